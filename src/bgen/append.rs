@@ -0,0 +1,108 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::bgi_writer::{index_is_current, update_index, INDEX_EXTENSIONS};
+use crate::bgen::chunks::Chunk;
+use crate::bgen::header::VARIANT_NUM_OFFSET;
+use crate::bgen::utils::write_u32;
+use color_eyre::{Report, Result};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Writes the variants of the stream, whose header has been read, at the end of the bgen
+/// file at `target_path`, and patches the number of variants of its header in place.
+/// Filters and chromosome renaming of the stream apply, data blocks are copied without
+/// being decoded. The `.bgi_rust` and `.bgi` indexes of the target are updated if they
+/// are up to date.
+/// Returns the number of variants appended.
+pub fn append_bgen<T: Read>(target_path: &str, mut bgen_stream: BgenStream<T>) -> Result<u32> {
+    let mut target_stream = BgenStream::from_path(target_path, false, false)?;
+    target_stream.read_offset_and_header()?;
+    check_compatible(&target_stream, &bgen_stream)?;
+    let target_header = target_stream.header.clone();
+    let layout_id = target_header.header_flags.layout_id;
+
+    // indexes already out of date are left as they are, the others get the new variants
+    let mut index_paths = Vec::new();
+    for extension in INDEX_EXTENSIONS {
+        let index_path = format!("{}{}", target_path, extension);
+        if Path::new(&index_path).exists() && index_is_current(target_path, &index_path)? {
+            index_paths.push(index_path);
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(target_path)?;
+    let start_offset = file.seek(SeekFrom::End(0))?;
+    let mut writer = BufWriter::new(file);
+    let mut num_appended = 0u32;
+    while let Some(raw_variant) = bgen_stream.next_raw_variant() {
+        raw_variant?.write_self(&mut writer, layout_id)?;
+        num_appended += 1;
+    }
+    let variant_num = target_header
+        .variant_num
+        .checked_add(num_appended)
+        .ok_or_else(|| Report::msg("Too many variants for a bgen file"))?;
+    // the header is patched last, so that an interrupted append leaves a readable file
+    let end_offset = writer.seek(SeekFrom::End(0))?;
+    writer.seek(SeekFrom::Start(VARIANT_NUM_OFFSET))?;
+    write_u32(&mut writer, variant_num)?;
+    writer.flush()?;
+    drop(writer);
+
+    // the file metadata of the indexes changes even when no variant was appended
+    for index_path in index_paths {
+        log::info!("Adding {} variants to {}", num_appended, index_path);
+        let mut appended_stream = BgenStream::from_path(target_path, false, false)?;
+        appended_stream.read_offset_and_header()?;
+        appended_stream.restrict_to_chunk(Chunk {
+            start_offset,
+            end_offset,
+            num_variants: num_appended,
+            ..Default::default()
+        })?;
+        update_index(appended_stream, &index_path)?;
+    }
+    Ok(num_appended)
+}
+
+fn check_compatible<T, U>(target: &BgenStream<T>, appended: &BgenStream<U>) -> Result<()> {
+    let target_flags = &target.header.header_flags;
+    let appended_flags = &appended.header.header_flags;
+    if target.header.sample_num != appended.header.sample_num {
+        return Err(Report::msg(format!(
+            "Cannot append variants on {} samples to a file with {} samples",
+            appended.header.sample_num, target.header.sample_num
+        )));
+    }
+    if target_flags.layout_id != appended_flags.layout_id {
+        return Err(Report::msg(format!(
+            "Cannot append variants in layout {} to a file in layout {}",
+            appended_flags.layout_id, target_flags.layout_id
+        )));
+    }
+    if target_flags.compressed_snp_blocks != appended_flags.compressed_snp_blocks {
+        return Err(Report::msg(
+            "Cannot append variants with a different compression of the data blocks",
+        ));
+    }
+    if target.samples.is_empty() || appended.samples.is_empty() {
+        return Ok(());
+    }
+    match target
+        .samples
+        .iter()
+        .zip(appended.samples.iter())
+        .position(|(t, a)| t != a)
+    {
+        Some(i) => Err(Report::msg(format!(
+            "Sample {} differs between the files: `{}` in the target, `{}` in the appended file",
+            i + 1,
+            target.samples[i],
+            appended.samples[i]
+        ))),
+        None => Ok(()),
+    }
+}
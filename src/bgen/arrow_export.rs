@@ -0,0 +1,294 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::variant_data::VariantData;
+use arrow::array::{
+    ArrayRef, Float64Builder, ListBuilder, StringBuilder, UInt32Builder, UInt64Builder,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use clap::ValueEnum;
+use color_eyre::{Report, Result};
+use itertools::Itertools;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+/// File format of the export
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Arrow IPC file
+    #[default]
+    Ipc,
+    Parquet,
+}
+
+/// Columns written for each sample, named after the sample identifiers, or `sample_0`,
+/// `sample_1`, ... for files without them
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SampleColumns {
+    /// Variant metadata only
+    #[default]
+    None,
+    /// Expected count of the second allele, null for variants that are not biallelic
+    Dosage,
+    /// List of the probabilities, as stored in the data block
+    Probabilities,
+}
+
+/// Exports the variants of a stream as Arrow IPC or Parquet, in record batches or row groups
+/// of `row_group_size` variants. Missing samples are null.
+///
+/// # Examples
+///
+/// ```
+/// use bgen_reader::bgen::arrow_export::{ArrowExporter, ExportFormat, SampleColumns};
+/// use bgen_reader::bgen::bgen_stream::BgenStream;
+///
+/// let bgen_bytes = include_bytes!("../../data_test/samp_100_var_100.bgen");
+/// let mut bgen_stream = BgenStream::from_bytes(bgen_bytes.to_vec(), true).unwrap();
+/// bgen_stream.read_offset_and_header().unwrap();
+/// let mut parquet = Vec::new();
+/// let variant_num = ArrowExporter::new(ExportFormat::Parquet)
+///     .with_sample_columns(SampleColumns::Dosage)
+///     .with_row_group_size(10)
+///     .export(&mut parquet, bgen_stream)
+///     .unwrap();
+/// assert_eq!(100, variant_num);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArrowExporter {
+    pub format: ExportFormat,
+    pub sample_columns: SampleColumns,
+    pub row_group_size: usize,
+}
+
+impl ArrowExporter {
+    pub const DEFAULT_ROW_GROUP_SIZE: usize = 10_000;
+    /// Names of the variant metadata columns, which sample columns cannot take
+    pub const METADATA_COLUMNS: [&'static str; 7] = [
+        "chr",
+        "pos",
+        "rsid",
+        "alternate_id",
+        "alleles",
+        "file_offset",
+        "size_in_bytes",
+    ];
+
+    pub fn new(format: ExportFormat) -> Self {
+        ArrowExporter {
+            format,
+            sample_columns: SampleColumns::None,
+            row_group_size: Self::DEFAULT_ROW_GROUP_SIZE,
+        }
+    }
+
+    pub fn with_sample_columns(mut self, sample_columns: SampleColumns) -> Self {
+        self.sample_columns = sample_columns;
+        self
+    }
+
+    pub fn with_row_group_size(mut self, row_group_size: usize) -> Self {
+        self.row_group_size = row_group_size;
+        self
+    }
+
+    /// Schema of the export: the variant metadata followed by one column per sample
+    pub fn schema(&self, samples: &[String]) -> SchemaRef {
+        let [chr, pos, rsid, alternate_id, alleles, file_offset, size_in_bytes] =
+            Self::METADATA_COLUMNS;
+        let mut fields = vec![
+            Field::new(chr, DataType::Utf8, false),
+            Field::new(pos, DataType::UInt32, false),
+            Field::new(rsid, DataType::Utf8, false),
+            Field::new(alternate_id, DataType::Utf8, false),
+            Field::new_list(alleles, Field::new_list_field(DataType::Utf8, true), false),
+            Field::new(file_offset, DataType::UInt64, false),
+            Field::new(size_in_bytes, DataType::UInt64, false),
+        ];
+        let sample_type = match self.sample_columns {
+            SampleColumns::None => None,
+            SampleColumns::Dosage => Some(DataType::Float64),
+            SampleColumns::Probabilities => Some(DataType::new_list(DataType::Float64, true)),
+        };
+        if let Some(sample_type) = sample_type {
+            fields.extend(
+                samples
+                    .iter()
+                    .map(|sample| Field::new(sample, sample_type.clone(), true)),
+            );
+        }
+        Arc::new(Schema::new(fields))
+    }
+
+    /// Writes the variants of the stream, whose header has been read, and returns the number
+    /// of variants written. The data blocks must be read for the sample columns.
+    pub fn export<T: Read>(
+        &self,
+        writer: impl Write + Send,
+        bgen_stream: BgenStream<T>,
+    ) -> Result<u64> {
+        if self.row_group_size == 0 {
+            return Err(Report::msg("The row group size must be at least 1"));
+        }
+        let samples = self.sample_column_names(&bgen_stream)?;
+        let schema = self.schema(&samples);
+        let mut batch_writer = BatchWriter::new(writer, schema.clone(), self)?;
+        let mut variant_num = 0;
+        for chunk in &bgen_stream.into_iter().chunks(self.row_group_size) {
+            let variants = chunk.collect::<Result<Vec<_>>>()?;
+            variant_num += variants.len() as u64;
+            batch_writer.write(&self.record_batch(schema.clone(), &variants, samples.len())?)?;
+        }
+        batch_writer.finish()?;
+        Ok(variant_num)
+    }
+
+    /// Sample identifiers of the stream, `sample_0`, `sample_1`, ... when it has none
+    fn sample_column_names<T: Read>(&self, bgen_stream: &BgenStream<T>) -> Result<Vec<String>> {
+        if self.sample_columns == SampleColumns::None {
+            return Ok(Vec::new());
+        }
+        let samples = if bgen_stream.samples.is_empty() {
+            (0..bgen_stream.header.sample_num)
+                .map(|i| format!("sample_{}", i))
+                .collect()
+        } else {
+            bgen_stream.samples.clone()
+        };
+        if let Some(sample) = samples
+            .iter()
+            .find(|sample| Self::METADATA_COLUMNS.contains(&sample.as_str()))
+        {
+            return Err(Report::msg(format!(
+                "Sample {} has the name of a variant column",
+                sample
+            )));
+        }
+        if let Some(sample) = samples.iter().duplicates().next() {
+            return Err(Report::msg(format!(
+                "Sample {} appears more than once",
+                sample
+            )));
+        }
+        Ok(samples)
+    }
+
+    fn record_batch(
+        &self,
+        schema: SchemaRef,
+        variants: &[VariantData],
+        sample_num: usize,
+    ) -> Result<RecordBatch> {
+        let mut chr = StringBuilder::new();
+        let mut pos = UInt32Builder::new();
+        let mut rsid = StringBuilder::new();
+        let mut alternate_id = StringBuilder::new();
+        let mut alleles = ListBuilder::new(StringBuilder::new());
+        let mut file_offset = UInt64Builder::new();
+        let mut size_in_bytes = UInt64Builder::new();
+        for variant in variants {
+            chr.append_value(&variant.chr);
+            pos.append_value(variant.pos);
+            rsid.append_value(&variant.rsid);
+            alternate_id.append_value(&variant.variants_id);
+            alleles.append_value(variant.alleles.iter().map(Some));
+            file_offset.append_value(variant.file_start_position as u64);
+            size_in_bytes.append_value(variant.size_in_bytes as u64);
+        }
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(chr.finish()),
+            Arc::new(pos.finish()),
+            Arc::new(rsid.finish()),
+            Arc::new(alternate_id.finish()),
+            Arc::new(alleles.finish()),
+            Arc::new(file_offset.finish()),
+            Arc::new(size_in_bytes.finish()),
+        ];
+        match self.sample_columns {
+            SampleColumns::None => {}
+            SampleColumns::Dosage => {
+                let mut dosages: Vec<_> = (0..sample_num).map(|_| Float64Builder::new()).collect();
+                for variant in variants {
+                    if variant.data_block.number_alleles != 2 {
+                        dosages.iter_mut().for_each(|builder| builder.append_null());
+                        continue;
+                    }
+                    for (builder, dosage) in dosages.iter_mut().zip(variant.data_block.dosages()?) {
+                        builder.append_option(dosage);
+                    }
+                }
+                columns.extend(
+                    dosages
+                        .iter_mut()
+                        .map(|builder| Arc::new(builder.finish()) as ArrayRef),
+                );
+            }
+            SampleColumns::Probabilities => {
+                let mut probabilities: Vec<_> = (0..sample_num)
+                    .map(|_| ListBuilder::new(Float64Builder::new()))
+                    .collect();
+                for variant in variants {
+                    for (builder, sample) in probabilities
+                        .iter_mut()
+                        .zip(variant.data_block.sample_probabilities())
+                    {
+                        if sample.missing {
+                            builder.append_null();
+                        } else {
+                            builder.append_value(sample.probabilities.into_iter().map(Some));
+                        }
+                    }
+                }
+                columns.extend(
+                    probabilities
+                        .iter_mut()
+                        .map(|builder| Arc::new(builder.finish()) as ArrayRef),
+                );
+            }
+        }
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+enum BatchWriter<W: Write + Send> {
+    Ipc(FileWriter<W>),
+    Parquet(ArrowWriter<W>),
+}
+
+impl<W: Write + Send> BatchWriter<W> {
+    fn new(writer: W, schema: SchemaRef, exporter: &ArrowExporter) -> Result<Self> {
+        Ok(match exporter.format {
+            ExportFormat::Ipc => BatchWriter::Ipc(FileWriter::try_new(writer, &schema)?),
+            ExportFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_max_row_group_size(exporter.row_group_size)
+                    .build();
+                BatchWriter::Parquet(ArrowWriter::try_new(writer, schema, Some(properties))?)
+            }
+        })
+    }
+
+    /// Writes the batch as its own record batch or row group
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            BatchWriter::Ipc(writer) => writer.write(batch)?,
+            BatchWriter::Parquet(writer) => {
+                writer.write(batch)?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            BatchWriter::Ipc(mut writer) => writer.finish()?,
+            BatchWriter::Parquet(writer) => {
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::variant_data::{DataBlock, VariantData};
+use color_eyre::{Report, Result};
+use ndarray::Array2;
+use std::io::Read;
+
+/// Values of a sample in a batch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchValues {
+    /// Expected count of the second allele, the rows of variants that are not biallelic are
+    /// `NaN`
+    #[default]
+    Dosage,
+    /// Probabilities as stored in the data block, every sample of the batch must have the
+    /// same ploidy and every variant the same number of alleles
+    Probabilities,
+}
+
+/// Variants of a batch and their values: one row per variant and, for the selected samples,
+/// one column per sample for dosages or consecutive columns per sample for probabilities.
+/// Missing samples are `NaN`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantBatch {
+    pub variants: Vec<VariantData>,
+    pub values: Array2<f32>,
+}
+
+/// Iterator on batches of `batch_size` variants of a stream, the last batch may be smaller.
+/// Filters collected on the stream select the window of variants.
+///
+/// # Examples
+///
+/// ```
+/// use bgen_reader::bgen::batch::{BatchReader, BatchValues};
+/// use bgen_reader::bgen::bgen_stream::BgenStream;
+///
+/// let bgen_bytes = include_bytes!("../../data_test/samp_100_var_100.bgen");
+/// let mut bgen_stream = BgenStream::from_bytes(bgen_bytes.to_vec(), true).unwrap();
+/// bgen_stream.read_offset_and_header().unwrap();
+/// let batch = BatchReader::new(bgen_stream, 10)
+///     .unwrap()
+///     .with_values(BatchValues::Probabilities)
+///     .with_sample_indices(vec![0, 5])
+///     .unwrap()
+///     .next()
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(&[10, 6], batch.values.shape());
+/// ```
+pub struct BatchReader<T> {
+    bgen_stream: BgenStream<T>,
+    batch_size: usize,
+    values: BatchValues,
+    sample_indices: Option<Vec<usize>>,
+}
+
+impl<T: Read> BatchReader<T> {
+    /// Batches of dosages of all the samples of the stream, whose header has been read and
+    /// whose data blocks are read
+    pub fn new(bgen_stream: BgenStream<T>, batch_size: usize) -> Result<Self> {
+        if batch_size == 0 {
+            return Err(Report::msg("The batch size must be at least 1"));
+        }
+        Ok(BatchReader {
+            bgen_stream,
+            batch_size,
+            values: BatchValues::Dosage,
+            sample_indices: None,
+        })
+    }
+
+    pub fn with_values(mut self, values: BatchValues) -> Self {
+        self.values = values;
+        self
+    }
+
+    /// Keeps the samples at the given indices, in the given order
+    pub fn with_sample_indices(mut self, sample_indices: Vec<usize>) -> Result<Self> {
+        let sample_num = self.bgen_stream.header.sample_num as usize;
+        if let Some(index) = sample_indices.iter().find(|&&index| index >= sample_num) {
+            return Err(Report::msg(format!(
+                "Sample index {} is out of range, the file has {} samples",
+                index, sample_num
+            )));
+        }
+        self.sample_indices = Some(sample_indices);
+        Ok(self)
+    }
+
+    /// Keeps the samples with the given identifiers, in the given order
+    pub fn with_samples(self, samples: &[String]) -> Result<Self> {
+        let sample_indices = samples
+            .iter()
+            .map(|sample| {
+                self.bgen_stream
+                    .samples
+                    .iter()
+                    .position(|s| s == sample)
+                    .ok_or_else(|| Report::msg(format!("Sample {} not in the file", sample)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.with_sample_indices(sample_indices)
+    }
+
+    fn batch(&self, variants: Vec<VariantData>) -> Result<VariantBatch> {
+        let rows = variants
+            .iter()
+            .map(|variant| match self.values {
+                BatchValues::Dosage => self.dosage_row(variant),
+                BatchValues::Probabilities => self.probability_row(&variant.data_block),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let columns = rows.first().map_or(0, Vec::len);
+        if let Some((variant, _)) = variants
+            .iter()
+            .zip(rows.iter())
+            .find(|(_, row)| row.len() != columns)
+        {
+            return Err(Report::msg(format!(
+                "Variant {} does not have the number of probabilities of the batch",
+                variant.rsid
+            )));
+        }
+        let values = Array2::from_shape_vec((rows.len(), columns), rows.concat())?;
+        Ok(VariantBatch { variants, values })
+    }
+
+    fn dosage_row(&self, variant: &VariantData) -> Result<Vec<f32>> {
+        let data_block = &variant.data_block;
+        if data_block.number_alleles != 2 {
+            let sample_num = data_block.number_individuals as usize;
+            return Ok(self.selected(sample_num).map(|_| f32::NAN).collect());
+        }
+        let dosages = data_block.dosages()?;
+        Ok(self
+            .selected(dosages.len())
+            .map(|index| dosages[index].map_or(f32::NAN, |dosage| dosage as f32))
+            .collect())
+    }
+
+    fn probability_row(&self, data_block: &DataBlock) -> Result<Vec<f32>> {
+        let samples = data_block.sample_probabilities();
+        let values_per_sample = data_block.probabilities_per_sample(data_block.maximum_ploidy);
+        let mut row = Vec::new();
+        for index in self.selected(samples.len()) {
+            let sample = &samples[index];
+            if sample.missing {
+                row.extend(std::iter::repeat_n(f32::NAN, values_per_sample));
+            } else if sample.probabilities.len() == values_per_sample {
+                row.extend(sample.probabilities.iter().map(|&p| p as f32));
+            } else {
+                return Err(Report::msg(format!(
+                    "Sample {} has ploidy {}, samples of a batch must all have ploidy {}",
+                    index, sample.ploidy, data_block.maximum_ploidy
+                )));
+            }
+        }
+        Ok(row)
+    }
+
+    fn selected(&self, sample_num: usize) -> Box<dyn Iterator<Item = usize> + '_> {
+        match &self.sample_indices {
+            Some(sample_indices) => Box::new(sample_indices.iter().copied()),
+            None => Box::new(0..sample_num),
+        }
+    }
+}
+
+impl<T: Read> Iterator for BatchReader<T> {
+    type Item = Result<VariantBatch>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let variants =
+            Iterator::take(&mut self.bgen_stream, self.batch_size).collect::<Result<Vec<_>>>();
+        match variants {
+            Ok(variants) if variants.is_empty() => None,
+            Ok(variants) => Some(self.batch(variants)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<T: Read> BgenStream<T> {
+    /// Batches of `batch_size` variants, see [`BatchReader`]
+    pub fn batches(self, batch_size: usize) -> Result<BatchReader<T>> {
+        BatchReader::new(self, batch_size)
+    }
+}
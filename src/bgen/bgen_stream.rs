@@ -1,14 +1,19 @@
-use crate::bgen::header::{Header, HeaderFlags};
-use crate::bgen::utils::{decompress_block, read_lines, write_u16, write_u32};
+use crate::bgen::chromosome::ChrRenamer;
+use crate::bgen::chunks::Chunk;
+use crate::bgen::header::{Header, HeaderFlags, HEADER_SIZE_WITHOUT_FREE_DATA, VARIANT_NUM_OFFSET};
+use crate::bgen::range_set::{RangeProgress, RangeSet};
+use crate::bgen::samples::{read_sample_file, resolve_samples, SampleFileEntry, SamplePolicy};
+use crate::bgen::utils::{create_output, decompress_block, read_lines, write_u16, write_u32};
 use crate::bgen::variant_data::{DataBlock, VariantData};
-use crate::parser::{FilterArgs, Range};
+use crate::parser::{AlleleFilters, FilterArgs, StatFilters};
 use bitvec::prelude::*;
 use color_eyre::{Report, Result};
-use itertools::Itertools;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::Once;
 use std::time::SystemTime;
 
 pub struct BgenStream<T> {
@@ -20,6 +25,29 @@ pub struct BgenStream<T> {
     pub ranges: Ranges,
     pub byte_count: usize,
     pub samples: Vec<String>,
+    pub sample_file: Vec<SampleFileEntry>,
+    pub sample_policy: SamplePolicy,
+    pub chr_renamer: Option<ChrRenamer>,
+    pub output_layout: Option<u8>,
+    pub output_bits: Option<u8>,
+    chunk: Option<Chunk>,
+}
+
+/// Variant with its data block kept as stored in the file, so that it can be copied
+/// without decoding and compressing it again
+#[derive(Clone, Debug)]
+pub struct RawVariant {
+    pub variant_data: VariantData,
+    pub raw_data_block: Vec<u8>,
+}
+
+impl RawVariant {
+    pub fn write_self(&self, writer: &mut impl Write, layout_id: u8) -> Result<()> {
+        self.variant_data
+            .write_identifying_block(writer, layout_id)?;
+        writer.write_all(&self.raw_data_block)?;
+        Ok(())
+    }
 }
 
 pub trait BgenClone<T> {
@@ -28,13 +56,43 @@ pub trait BgenClone<T> {
 
 #[derive(Clone, Default, Debug)]
 pub struct Ranges {
-    pub incl_range: Vec<Range>,
-    pub incl_rsids: Vec<String>,
-    pub excl_range: Vec<Range>,
-    pub excl_rsids: Vec<String>,
+    pub incl_range: RangeSet,
+    pub incl_rsids: HashSet<String>,
+    pub excl_range: RangeSet,
+    pub excl_rsids: HashSet<String>,
+    pub incl_variant_ids: HashSet<String>,
+    pub excl_variant_ids: HashSet<String>,
+    pub incl_variant_keys: HashSet<String>,
+    pub excl_variant_keys: HashSet<String>,
+    pub unordered_alleles: bool,
+    pub stat_filters: StatFilters,
+    pub allele_filters: AlleleFilters,
+    pub assume_sorted: bool,
+    progress: RangeProgress,
+}
+
+impl Ranges {
+    pub fn has_inclusion(&self) -> bool {
+        !(self.incl_range.is_empty()
+            && self.incl_rsids.is_empty()
+            && self.incl_variant_ids.is_empty()
+            && self.incl_variant_keys.is_empty())
+    }
+
+    /// With a position sorted input and only ranges to include, tells whether the stream
+    /// went past every included range, in which case no later variant can be selected
+    pub fn passed_included_ranges(&mut self, chr: &str, pos: u32) -> bool {
+        self.assume_sorted
+            && !self.incl_range.is_empty()
+            && self.incl_rsids.is_empty()
+            && self.incl_variant_ids.is_empty()
+            && self.incl_variant_keys.is_empty()
+            && self.progress.passed_all(&self.incl_range, chr, pos)
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileMetadata {
     pub filename: String,
     pub path: String,
@@ -45,14 +103,18 @@ pub struct FileMetadata {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BytesMetadata {
     bytes: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MetadataBgi {
     File(FileMetadata),
     Bytes(BytesMetadata),
+    /// Input that can only be read once, such as the standard input
+    Stream,
 }
 
 macro_rules! read_into_buffer {
@@ -71,10 +133,21 @@ macro_rules! read_into_vector {
 }
 
 impl<T: Read> BgenStream<T> {
+    /// Stream over any reader, such as the standard input or a decompressing reader. The
+    /// input is read once and never buffered whole, so the stream cannot be cloned.
+    pub fn from_reader(reader: T, read_data_block: bool) -> Self {
+        BgenStream::new(
+            BufReader::new(reader),
+            MetadataBgi::Stream,
+            vec![],
+            read_data_block,
+        )
+    }
+
     pub fn new(
         stream: BufReader<T>,
         metadata: MetadataBgi,
-        samples: Vec<String>,
+        sample_file: Vec<SampleFileEntry>,
         read_data_block: bool,
     ) -> Self {
         let header = Header::default();
@@ -87,7 +160,13 @@ impl<T: Read> BgenStream<T> {
             ranges,
             byte_count: 0,
             metadata,
-            samples,
+            samples: Vec::new(),
+            sample_file,
+            sample_policy: SamplePolicy::default(),
+            chr_renamer: None,
+            output_layout: None,
+            output_bits: None,
+            chunk: None,
         }
     }
 
@@ -100,7 +179,7 @@ impl<T: Read> BgenStream<T> {
         log::info!("start_data_offset: {}", start_data_offset);
         let header_size = self.read_u32()?;
         log::info!("Header size: {}", header_size);
-        if header_size < 20 {
+        if header_size < HEADER_SIZE_WITHOUT_FREE_DATA {
             return Err(Report::msg(
                 "Header size of bgen is less than 20. The data is most likely corrupted",
             ));
@@ -120,12 +199,23 @@ impl<T: Read> BgenStream<T> {
                 "Magic number in header is not correct. The data is most likely corrupted",
             ));
         }
-        self.skip_bytes(header_size as usize - 20)?;
+        let free_data =
+            self.read_vector_length((header_size - HEADER_SIZE_WITHOUT_FREE_DATA) as usize)?;
         let header_flags = HeaderFlags::from_u32(self.read_u32()?)?;
         log::info!("Layout id: {}", header_flags.layout_id);
         log::info!("sample_id_present: {}", header_flags.sample_id_present);
-        if header_flags.sample_id_present {
-            self.read_samples()?;
+        let embedded_samples = if header_flags.sample_id_present {
+            Some(self.read_samples()?)
+        } else {
+            None
+        };
+        self.samples = resolve_samples(embedded_samples, &self.sample_file, self.sample_policy)?;
+        if !self.samples.is_empty() && self.samples.len() != sample_num as usize {
+            return Err(Report::msg(format!(
+                "Header announces {} samples but {} sample identifiers were found",
+                sample_num,
+                self.samples.len()
+            )));
         }
 
         log::info!("byte_count: {}", self.byte_count);
@@ -145,31 +235,146 @@ impl<T: Read> BgenStream<T> {
             variant_count: 0,
             sample_num,
             header_flags,
+            free_data,
         };
         Ok(())
     }
 
-    pub fn read_samples(&mut self) -> Result<()> {
+    pub fn read_samples(&mut self) -> Result<Vec<String>> {
         let len_samples_block = self.read_u32()?;
         let num_samples = self.read_u32()?;
-        let new_samples: Vec<_> = (0..num_samples)
+        let samples: Vec<_> = (0..num_samples)
             .map(|_| {
                 let length_s = self.read_u16()?;
                 self.read_string(length_s as usize)
             })
             .collect::<Result<Vec<_>>>()?;
-        if !self.samples.is_empty() {
-            assert_eq!(
-                self.samples, new_samples,
-                "Samples embedded in bgen file and in .sample file do not match."
-            );
-        }
         self.len_samples_block = len_samples_block;
-        self.samples = new_samples;
-        Ok(())
+        Ok(samples)
+    }
+
+    pub fn with_sample_policy(mut self, sample_policy: SamplePolicy) -> Self {
+        self.sample_policy = sample_policy;
+        self
+    }
+
+    pub fn with_chr_renamer(mut self, chr_renamer: Option<ChrRenamer>) -> Self {
+        self.chr_renamer = chr_renamer;
+        self
+    }
+
+    /// Layout of the variants written by `to_bgen`, the layout of the stream by default
+    pub fn with_output_layout(mut self, output_layout: Option<u8>) -> Self {
+        self.output_layout = output_layout;
+        self
+    }
+
+    /// Number of bits of the probabilities written by `to_bgen` in layout 2, the number of
+    /// bits of each variant by default
+    pub fn with_output_bits(mut self, output_bits: Option<u8>) -> Self {
+        self.output_bits = output_bits;
+        self
+    }
+
+    /// Same as `next` for the iterator, but the data block of the variant is kept as
+    /// stored in the file. It is only decoded if statistics filters need it.
+    pub fn next_raw_variant(&mut self) -> Option<Result<RawVariant>> {
+        let raw_variant = self.next_filtered(Self::read_filtered_raw_variant, |raw_variant| {
+            &mut raw_variant.variant_data
+        })?;
+        Some(raw_variant.map(|mut raw_variant| {
+            raw_variant.variant_data.data_block = DataBlock::default();
+            raw_variant
+        }))
+    }
+
+    /// Reads variants with `read_variant` up to the first one that passes the filters, and
+    /// renames its chromosome. `variant_data` gives the variant data of what was read.
+    fn next_filtered<V>(
+        &mut self,
+        read_variant: fn(&mut Self) -> Result<V>,
+        variant_data: fn(&mut V) -> &mut VariantData,
+    ) -> Option<Result<V>> {
+        while self.header.variant_count < self.header.variant_num {
+            match self.enter_chunk() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+            let mut variant = match read_variant(self) {
+                Ok(variant) => variant,
+                Err(e) => {
+                    // the input cannot be read past a variant that failed
+                    self.header.variant_count = self.header.variant_num;
+                    return Some(Err(e));
+                }
+            };
+            self.header.variant_count += 1;
+            let var_data = variant_data(&mut variant);
+            if self
+                .ranges
+                .passed_included_ranges(&var_data.chr, var_data.pos)
+            {
+                // skip the remaining variants without reading them
+                self.header.variant_count = self.header.variant_num;
+                return None;
+            }
+            if var_data.filter_with_args(&self.ranges) {
+                self.rename_chr(var_data);
+                return Some(Ok(variant));
+            }
+        }
+        None
+    }
+
+    /// Moves to the start of the chunk the stream is restricted to, and tells whether the
+    /// next variant is in the chunk
+    fn enter_chunk(&mut self) -> Result<bool> {
+        let Some(chunk) = &self.chunk else {
+            return Ok(true);
+        };
+        let (start_offset, end_offset) = (chunk.start_offset as usize, chunk.end_offset as usize);
+        if self.byte_count < start_offset {
+            self.skip_bytes(start_offset - self.byte_count)?;
+        }
+        Ok(self.byte_count < end_offset)
+    }
+
+    fn rename_chr(&self, variant_data: &mut VariantData) {
+        if let Some(chr_renamer) = &self.chr_renamer {
+            variant_data.chr = chr_renamer.rename(&variant_data.chr);
+        }
     }
 
     fn read_variant_data(&mut self) -> Result<VariantData> {
+        let mut variant_data = self.read_identifying_block()?;
+        // variants rejected on their alleles are never decoded
+        if self.read_data_block && self.ranges.allele_filters.accept(&variant_data.alleles) {
+            let raw_data_block = self.read_raw_data_block(variant_data.number_individuals)?;
+            variant_data.data_block = self.decode_data_block(&raw_data_block)?;
+        } else {
+            self.skip_data_block(variant_data.number_individuals)?;
+        }
+        variant_data.size_in_bytes = self.byte_count - variant_data.file_start_position;
+        Ok(variant_data)
+    }
+
+    /// Reads a variant, keeping its data block as stored in the file. The data block is
+    /// also decoded when the statistics filters need it.
+    fn read_filtered_raw_variant(&mut self) -> Result<RawVariant> {
+        let mut variant_data = self.read_identifying_block()?;
+        let raw_data_block = self.read_raw_data_block(variant_data.number_individuals)?;
+        variant_data.size_in_bytes = self.byte_count - variant_data.file_start_position;
+        if !self.ranges.stat_filters.is_empty() {
+            variant_data.data_block = self.decode_data_block(&raw_data_block)?;
+        }
+        Ok(RawVariant {
+            variant_data,
+            raw_data_block,
+        })
+    }
+
+    fn read_identifying_block(&mut self) -> Result<VariantData> {
         let file_start_position = self.byte_count;
         let layout_id = self.header.header_flags.layout_id;
         let number_individuals = if layout_id == 1 {
@@ -182,54 +387,80 @@ impl<T: Read> BgenStream<T> {
         let chr = self.read_u16_sized_string()?;
         let pos = self.read_u32()?;
         let num_alleles = if layout_id == 1 { 2 } else { self.read_u16()? };
-        let alleles: Result<Vec<String>> = (0..num_alleles)
+        let alleles: Vec<String> = (0..num_alleles)
             .map(|_| self.read_u32_sized_string())
-            .collect();
-        let data_block = if self.read_data_block {
-            self.read_data_block()?
-        } else {
-            let bytes_until_next_data_block = self.read_u32()?;
-            self.skip_bytes(bytes_until_next_data_block as usize)?;
-            DataBlock::default()
-        };
-        let file_end_position = self.byte_count;
-        let size_in_bytes = file_end_position - file_start_position;
-        let variant_data = VariantData {
+            .collect::<Result<Vec<_>>>()?;
+        Ok(VariantData {
             number_individuals,
             variants_id,
             rsid,
             chr,
             pos,
             number_alleles: num_alleles,
-            alleles: alleles?,
+            alleles,
             file_start_position,
-            size_in_bytes,
-            data_block,
+            size_in_bytes: 0,
+            data_block: DataBlock::default(),
+        })
+    }
+
+    /// Length of the data block when it is not stored with a length prefix, which only
+    /// happens for uncompressed layout 1 blocks
+    fn unprefixed_data_block_length(&self, number_individuals: Option<u32>) -> Option<usize> {
+        let header_flags = &self.header.header_flags;
+        if header_flags.layout_id == 1 && !header_flags.compressed_snp_blocks {
+            Some(6 * number_individuals.unwrap_or(self.header.sample_num) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn skip_data_block(&mut self, number_individuals: Option<u32>) -> Result<()> {
+        let length = match self.unprefixed_data_block_length(number_individuals) {
+            Some(length) => length,
+            None => self.read_u32()? as usize,
         };
-        Ok(variant_data)
+        self.skip_bytes(length)
     }
 
-    fn read_data_block(&mut self) -> Result<DataBlock> {
-        assert_eq!(
-            self.header.header_flags.layout_id, 2,
-            "Layouts other than 2 are not yet supported"
-        );
+    /// Data block bytes as stored in the file, including the length prefix
+    fn read_raw_data_block(&mut self, number_individuals: Option<u32>) -> Result<Vec<u8>> {
+        if let Some(length) = self.unprefixed_data_block_length(number_individuals) {
+            return self.read_vector_length(length);
+        }
         let length_data_block = self.read_u32()?;
-        let compressed_snp_blocks = self.header.header_flags.compressed_snp_blocks;
-        let uncompressed_length = if compressed_snp_blocks {
-            self.read_u32()?
+        let mut raw_data_block = length_data_block.to_le_bytes().to_vec();
+        raw_data_block.extend(self.read_vector_length(length_data_block as usize)?);
+        Ok(raw_data_block)
+    }
+
+    fn decode_data_block(&self, raw_data_block: &[u8]) -> Result<DataBlock> {
+        let truncated = || {
+            Report::msg(format!(
+                "Data block of {} bytes is too short to be decoded",
+                raw_data_block.len()
+            ))
+        };
+        if self.header.header_flags.layout_id == 1 {
+            let uncompressed_block = if self.header.header_flags.compressed_snp_blocks {
+                let uncompressed_length = 6 * self.header.sample_num as usize;
+                let compressed_block = raw_data_block.get(4..).ok_or_else(truncated)?;
+                decompress_block(compressed_block.to_vec(), uncompressed_length)?
+            } else {
+                raw_data_block.to_vec()
+            };
+            return DataBlock::from_layout_1_bytes(&uncompressed_block);
+        }
+        let block = raw_data_block.get(4..).ok_or_else(truncated)?;
+        let uncompressed_block = if self.header.header_flags.compressed_snp_blocks {
+            let (length_bytes, compressed_block) =
+                block.split_at_checked(4).ok_or_else(truncated)?;
+            let uncompressed_length = u32::from_le_bytes(length_bytes.try_into()?) as usize;
+            // fails unless exactly `uncompressed_length` bytes are decompressed
+            decompress_block(compressed_block.to_vec(), uncompressed_length)?
         } else {
-            length_data_block
+            block.to_vec()
         };
-        // TODO(lhenches): handle other cases
-        assert!(compressed_snp_blocks);
-        let compressed_block = self.read_vector_length((length_data_block - 4) as usize)?;
-        let uncompressed_block = decompress_block(compressed_block, uncompressed_length as usize)?;
-        assert_eq!(
-            uncompressed_block.len(),
-            uncompressed_length as usize,
-            "Uncompressed data length is expected to be the same as computed uncompressed length"
-        );
         Self::build_from_uncompressed_block(uncompressed_block)
     }
 
@@ -258,10 +489,13 @@ impl<T: Read> BgenStream<T> {
                 .map(|c| Self::convert_u8_chunk(c))
                 .collect()
         } else {
-            println!("Warning: the probability stored are not a multiple of 8");
+            static NOT_BYTE_ALIGNED: Once = Once::new();
+            NOT_BYTE_ALIGNED.call_once(|| {
+                log::warn!("The probabilities are not stored on a multiple of 8 bits")
+            });
             let iterate_bits = remaining_bytes.view_bits::<Lsb0>();
             iterate_bits
-                .chunks(bytes_probability as usize)
+                .chunks_exact(bytes_probability as usize)
                 .map(|c| Self::convert_u32(c))
                 .collect()
         };
@@ -280,7 +514,6 @@ impl<T: Read> BgenStream<T> {
         Ok(data_block)
     }
 
-
     fn convert_u8_chunk(to_convert: &[u8]) -> u32 {
         to_convert
             .iter()
@@ -340,23 +573,41 @@ impl<T: Read> BgenStream<T> {
             &mut std::io::Read::take(std::io::Read::by_ref(self), num_bytes.try_into()?),
             &mut io::sink(),
         )?;
+        self.add_counter(num_bytes);
         Ok(())
     }
 
     pub fn collect_filters(&mut self, list_args: FilterArgs) -> Result<()> {
         let (vec_incl_range, vec_incl_rsid, vec_excl_range, vec_excl_rsid) =
             list_args.get_vector_incl_and_excl()?;
-        self.ranges.incl_range = vec_incl_range;
-        self.ranges.incl_rsids = vec_incl_rsid;
-        self.ranges.excl_range = vec_excl_range;
-        self.ranges.excl_rsids = vec_excl_rsid;
+        self.ranges.incl_range = RangeSet::new(&vec_incl_range);
+        self.ranges.incl_rsids = vec_incl_rsid.into_iter().collect();
+        self.ranges.excl_range = RangeSet::new(&vec_excl_range);
+        self.ranges.excl_rsids = vec_excl_rsid.into_iter().collect();
+        let (vec_incl_id, vec_excl_id, vec_incl_key, vec_excl_key) =
+            list_args.get_variant_ids_incl_and_excl()?;
+        self.ranges.incl_variant_ids = vec_incl_id.into_iter().collect();
+        self.ranges.excl_variant_ids = vec_excl_id.into_iter().collect();
+        self.ranges.incl_variant_keys = vec_incl_key.into_iter().collect();
+        self.ranges.excl_variant_keys = vec_excl_key.into_iter().collect();
+        self.ranges.unordered_alleles = list_args.unordered_alleles;
+        self.ranges.assume_sorted = list_args.assume_sorted;
+        self.ranges.stat_filters = list_args.stat_filters;
+        self.ranges.allele_filters = list_args.allele_filters;
+        // statistics filters need the decoded data block
+        if !self.ranges.stat_filters.is_empty() {
+            self.read_data_block = true;
+        }
         Ok(())
     }
 }
 
-pub fn bgen_merge(merge_filename: String, output_name: String, cli_filename: String) -> Result<()> {
-    let file = File::create(output_name)?;
-    let mut writer = BufWriter::new(file);
+pub fn bgen_merge(
+    merge_filename: String,
+    output_name: String,
+    cli_filename: String,
+    chr_renamer: Option<ChrRenamer>,
+) -> Result<()> {
     let mut lines = read_lines(merge_filename.clone())?;
     if !lines.contains(&cli_filename) {
         lines.push(cli_filename)
@@ -364,23 +615,27 @@ pub fn bgen_merge(merge_filename: String, output_name: String, cli_filename: Str
     lines.retain(|s| !s.is_empty());
     let mut num_variants = 0;
     // first pass to read
-    println!("First pass for merging, computing the number of variants and checking samples");
+    log::info!("First pass for merging, computing the number of variants and checking samples");
     let mut samples = Vec::new();
     for (i, line) in lines.iter().enumerate() {
-        println!("Reading file {}, at line {} in merge file", line, i);
+        log::info!("Reading file {}, at line {} in merge file", line, i);
         let mut bgen_stream = BgenStream::from_path(line, false, false)?;
         bgen_stream.read_offset_and_header()?;
         num_variants += bgen_stream.header.variant_num;
         if i == 0 {
             samples = bgen_stream.samples;
-        } else {
-            assert_eq!(samples, bgen_stream.samples)
+        } else if samples != bgen_stream.samples {
+            return Err(Report::msg(format!(
+                "Samples of {} differ from those of {}, files to merge must have the same samples",
+                line, lines[0]
+            )));
         }
     }
 
-    println!("Second pass for merging, writing variant data");
+    let mut writer = create_output(&output_name)?;
+    log::info!("Second pass for merging, writing variant data");
     for (i, line) in lines.iter().enumerate() {
-        println!("Reading file {}, at line {}", line, i);
+        log::info!("Reading file {}, at line {}", line, i);
         let mut bgen_stream = BgenStream::from_path(line, false, true)?;
         bgen_stream.read_offset_and_header()?;
         if i == 0 {
@@ -393,6 +648,15 @@ pub fn bgen_merge(merge_filename: String, output_name: String, cli_filename: Str
                 bgen_stream.len_samples_block,
             )?;
         }
+        if chr_renamer.is_some() {
+            // variants are rewritten one by one, the data blocks are copied as is
+            let layout_id = bgen_stream.header.header_flags.layout_id;
+            let mut bgen_stream = bgen_stream.with_chr_renamer(chr_renamer.clone());
+            while let Some(raw_variant) = bgen_stream.next_raw_variant() {
+                raw_variant?.write_self(&mut writer, layout_id)?;
+            }
+            continue;
+        }
         let mut buf = [0; 8192];
         loop {
             let len_read = bgen_stream.read(&mut buf)?;
@@ -403,34 +667,20 @@ pub fn bgen_merge(merge_filename: String, output_name: String, cli_filename: Str
             }
         }
     }
+    writer.flush()?;
     Ok(())
 }
 
 impl<T: Read> Iterator for BgenStream<T> {
     type Item = Result<VariantData>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.header.variant_count >= self.header.variant_num {
-            None
-        } else {
-            while let Ok(var_data) = self.read_variant_data() {
-                self.header.variant_count += 1;
-                if var_data.filter_with_args(
-                    &self.ranges.incl_range,
-                    &self.ranges.incl_rsids,
-                    &self.ranges.excl_range,
-                    &self.ranges.excl_rsids,
-                ) {
-                    return Some(Ok(var_data));
-                }
-            }
-            None
-        }
+        self.next_filtered(Self::read_variant_data, |variant_data| variant_data)
     }
 }
 
 pub fn write_samples(
     samples: &[String],
-    writer: &mut BufWriter<File>,
+    writer: &mut impl Write,
     len_samples_block: u32,
 ) -> Result<()> {
     write_u32(writer, len_samples_block)?;
@@ -443,94 +693,293 @@ pub fn write_samples(
     Ok(())
 }
 
+/// Writes the header of `header` announcing `variant_num` variants, followed by the samples
+/// block unless `no_samples` is set or there are no samples
+pub fn write_header_and_samples(
+    writer: &mut impl Write,
+    header: &Header,
+    samples: &[String],
+    variant_num: u32,
+    no_samples: bool,
+) -> Result<()> {
+    let mut header_final = header.clone();
+    header_final.variant_num = variant_num;
+    header_final.header_flags.sample_id_present = !no_samples && !samples.is_empty();
+    let len_samples_block = samples_block_length(samples);
+    header_final.header_size = HEADER_SIZE_WITHOUT_FREE_DATA + header_final.free_data.len() as u32;
+    header_final.start_data_offset = header_final.header_size;
+    if header_final.header_flags.sample_id_present {
+        header_final.start_data_offset += len_samples_block;
+    }
+    header_final.write_header(writer)?;
+    if header_final.header_flags.sample_id_present {
+        write_samples(samples, writer, len_samples_block)?;
+    }
+    Ok(())
+}
+
+pub fn samples_block_length(samples: &[String]) -> u32 {
+    8u32 + samples.iter().map(|s| s.len() as u32 + 2u32).sum::<u32>()
+}
+
+impl<T: Read> BgenStream<T> {
+    /// Writes the variants of the stream in the bgen format to a seekable `writer`, in a
+    /// single pass: the header announces no variant until it is patched at the end.
+    /// Returns the same as [`BgenStream::to_bgen`].
+    pub fn write_bgen_seekable<W: Write + Seek>(
+        mut self,
+        mut writer: W,
+        no_samples: bool,
+    ) -> Result<Option<RequantisationError>> {
+        let header = self.output_header()?;
+        let samples = std::mem::take(&mut self.samples);
+        self.read_data_block = true;
+        let start_position = writer.stream_position()?;
+        write_header_and_samples(&mut writer, &header, &samples, 0, no_samples)?;
+        let output_bits = self.output_bits;
+        let (num_variants, requantisation_error) =
+            write_variants(&mut self, &mut writer, &header.header_flags, output_bits)?;
+        let end_position = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(start_position + VARIANT_NUM_OFFSET))?;
+        write_u32(&mut writer, num_variants)?;
+        writer.seek(SeekFrom::Start(end_position))?;
+        Ok(output_bits.map(|_| requantisation_error))
+    }
+
+    /// Header of the bgen output of the stream, in the layout asked for
+    fn output_header(&self) -> Result<Header> {
+        let mut header = self.header.clone();
+        if let Some(layout_id) = self.output_layout {
+            if !(1..=2).contains(&layout_id) {
+                return Err(Report::msg(format!("Unknown layout {}", layout_id)));
+            }
+            header.header_flags.layout_id = layout_id;
+        }
+        if self.output_bits.is_some() && header.header_flags.layout_id == 1 {
+            return Err(Report::msg(
+                "Layout 1 always stores probabilities on 16 bits",
+            ));
+        }
+        Ok(header)
+    }
+}
+
+/// Rounding caused by storing the probabilities on a new number of bits
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RequantisationError {
+    /// Largest change of a dosage of a biallelic variant
+    pub max_dosage_error: f64,
+    /// Requantised variants left out of the dosage error, as they are not biallelic
+    pub num_not_biallelic: u32,
+}
+
+/// Writes the variants in the layout of `header_flags`, re-quantised on `output_bits` bits
+/// if given. Returns the number of variants and the largest change of a dosage.
+fn write_variants(
+    variants: impl Iterator<Item = Result<VariantData>>,
+    writer: &mut impl Write,
+    header_flags: &HeaderFlags,
+    output_bits: Option<u8>,
+) -> Result<(u32, RequantisationError)> {
+    let mut num_variants = 0u32;
+    let mut requantisation_error = RequantisationError::default();
+    for variant_data in variants {
+        let mut var_data = variant_data?;
+        if let Some(bits) = output_bits {
+            if bits != var_data.data_block.bytes_probability {
+                let requantised = var_data.data_block.with_bits(bits)?;
+                match var_data.data_block.max_dosage_error(&requantised) {
+                    Some(error) => {
+                        requantisation_error.max_dosage_error =
+                            requantisation_error.max_dosage_error.max(error)
+                    }
+                    None => requantisation_error.num_not_biallelic += 1,
+                }
+                var_data.data_block = requantised;
+            }
+        }
+        var_data.write_self(writer, header_flags)?;
+        num_variants = num_variants
+            .checked_add(1)
+            .ok_or_else(|| Report::msg("Too many variants for a bgen file"))?;
+    }
+    Ok((num_variants, requantisation_error))
+}
+
 impl<T: Read> BgenStream<T>
 where
     BgenStream<T>: BgenClone<T>,
 {
-    pub fn to_bgen(mut self, output_path: &str, no_samples: bool) -> Result<()> {
-        let file = File::create(output_path)?;
-        let mut writer = BufWriter::new(file);
+    /// Writes the variants of the stream to a bgen file, `-` stands for the standard output.
+    /// When the probabilities are stored on a new number of bits, returns the largest change
+    /// of a dosage of a biallelic variant caused by the rounding, and the number of variants
+    /// left out of it.
+    pub fn to_bgen(
+        self,
+        output_path: &str,
+        no_samples: bool,
+    ) -> Result<Option<RequantisationError>> {
+        if output_path == "-" {
+            // the standard output cannot be seeked, so the input is read twice
+            if matches!(self.metadata, MetadataBgi::Stream) {
+                return Err(Report::msg(
+                    "Bgen read from a stream cannot be written to the standard output, write it to a file instead",
+                ));
+            }
+            let mut writer = create_output(output_path)?;
+            let requantisation_error = self.write_bgen(&mut writer, no_samples)?;
+            writer.flush()?;
+            return Ok(requantisation_error);
+        }
+        let mut writer = BufWriter::new(File::create(output_path)?);
+        let requantisation_error = self.write_bgen_seekable(&mut writer, no_samples)?;
+        writer.flush()?;
+        Ok(requantisation_error)
+    }
+
+    /// Writes the variants of the stream in the bgen format to `writer`, which cannot be
+    /// seeked, so that the input is read twice: once to count the variants announced by the
+    /// header, and once to write them. See [`BgenStream::write_bgen_seekable`] otherwise.
+    pub fn write_bgen(
+        mut self,
+        mut writer: impl Write,
+        no_samples: bool,
+    ) -> Result<Option<RequantisationError>> {
         let mut other = self.create_identical_bgen()?;
         other.read_offset_and_header()?;
-        self.read_data_block = false;
+        self.read_data_block = !self.ranges.stat_filters.is_empty();
+        // samples resolved by the policy of this stream take precedence over the embedded ones
+        let samples = std::mem::take(&mut self.samples);
+        let header = self.output_header()?;
+        let output_bits = self.output_bits;
         // first pass to get the number of variants
-        let mut header_final = self.header.clone();
-        let num_variants = self.count();
-        header_final.variant_num = num_variants as u32;
-        if no_samples {
-            header_final.header_flags.sample_id_present = false;
-            header_final.start_data_offset -= 8u32
-                + other
-                    .samples
-                    .iter()
-                    .map(|s| s.len() as u32 + 2u32)
-                    .sum::<u32>();
+        let num_variants = self.try_fold(0u32, |num_variants, variant_data| {
+            variant_data.map(|_| num_variants + 1)
+        })?;
+        write_header_and_samples(&mut writer, &header, &samples, num_variants, no_samples)?;
+        let (num_written, requantisation_error) =
+            write_variants(other, &mut writer, &header.header_flags, output_bits)?;
+        // the header is already written, the count of the second pass must match it
+        if num_written != num_variants {
+            return Err(Report::msg(format!(
+                "The bgen file changed while it was copied: {} variants counted, {} written",
+                num_variants, num_written
+            )));
+        }
+        Ok(output_bits.map(|_| requantisation_error))
+    }
+}
+
+impl<T: Read + Seek> BgenStream<T> {
+    /// Restricts the stream to the variants of a chunk. Once the header has been read, the
+    /// stream seeks to the start of the chunk instead of reading up to it.
+    pub fn restrict_to_chunk(&mut self, chunk: Chunk) -> Result<()> {
+        let data_start = self.header.start_data_offset as usize + 4;
+        if self.byte_count > 0 && (chunk.start_offset as usize) < data_start {
+            return Err(Report::msg(format!(
+                "Chunk starts at byte {}, before the first variant at byte {}",
+                chunk.start_offset, data_start
+            )));
         }
-        header_final.write_header(&mut writer)?;
-        if header_final.header_flags.sample_id_present {
-           write_samples(&other.samples, &mut writer, other.len_samples_block)?;
+        if self.byte_count > 0 && self.byte_count <= chunk.start_offset as usize {
+            self.stream.seek(SeekFrom::Start(chunk.start_offset))?;
+            self.byte_count = chunk.start_offset as usize;
         }
-        let layout_id = other.header.header_flags.layout_id;
-        other.try_for_each(|variant_data| {
-            let var_data = variant_data?;
-            var_data.write_self(&mut writer, layout_id)
-        })
+        self.chunk = Some(chunk);
+        Ok(())
     }
 }
 
 impl BgenStream<File> {
     pub fn from_path(path_str: &str, use_sample_file: bool, read_data_block: bool) -> Result<Self> {
-        // Build metadata for file
-        let path = Path::new(path_str);
-        let filename = path.file_name().ok_or(Report::msg(format!(
-            "File name cannot be extracted from {}",
-            path_str
-        )))?;
-        let sample_path = path.with_extension("sample");
-        let samples = if let Ok(file) = File::open(sample_path) {
-            if use_sample_file {
-                println!("Reading samples from .sample file");
-                let samples_reader = BufReader::new(file);
-                samples_reader
-                    .lines()
-                    .skip(2)
-                    .flatten()
-                    .map(|line| line.split_whitespace().take(2).join(" "))
-                    .collect()
-            } else {
-                vec![]
-            }
-        } else {
-            vec![]
-        };
+        let (file, metadata_file, sample_file) = open_file(path_str, use_sample_file)?;
+        Ok(BgenStream::new(
+            BufReader::new(file),
+            MetadataBgi::File(metadata_file),
+            sample_file,
+            read_data_block,
+        ))
+    }
+}
 
-        let metadata_std = std::fs::metadata(path)?;
-        let file_size = metadata_std.len();
-        let index_creation_time = metadata_std.created().unwrap_or(SystemTime::UNIX_EPOCH);
-        let last_write_time = metadata_std.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-        let mut first_1000_bytes = vec![0; 1000];
-        let mut file = File::open(path_str)?;
-        file.read_exact(first_1000_bytes.as_mut_slice())?;
-
-        let file = File::open(path_str)?;
-        let stream = BufReader::new(file);
-        let metadata_file = FileMetadata {
-            filename: filename.to_str().unwrap().to_string(),
-            path: path_str.to_string(),
-            file_size,
-            index_creation_time,
-            first_1000_bytes,
-            last_write_time,
-        };
+/// Opens a bgen file, with its metadata and the content of its .sample file if asked for
+fn open_file(
+    path_str: &str,
+    use_sample_file: bool,
+) -> Result<(File, FileMetadata, Vec<SampleFileEntry>)> {
+    // Build metadata for file
+    let path = Path::new(path_str);
+    let filename = path.file_name().ok_or(Report::msg(format!(
+        "File name cannot be extracted from {}",
+        path_str
+    )))?;
+    let sample_path = path.with_extension("sample");
+    let sample_file = if use_sample_file && sample_path.exists() {
+        log::info!("Reading samples from .sample file");
+        read_sample_file(sample_path)?
+    } else {
+        vec![]
+    };
+
+    let metadata_std = std::fs::metadata(path)?;
+    let file_size = metadata_std.len();
+    let index_creation_time = metadata_std.created().unwrap_or(SystemTime::UNIX_EPOCH);
+    let last_write_time = metadata_std.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    // files smaller than 1000 bytes are read whole
+    let mut first_1000_bytes = Vec::with_capacity(1000);
+    let mut file = File::open(path_str)?;
+    Read::by_ref(&mut file)
+        .take(1000)
+        .read_to_end(&mut first_1000_bytes)?;
+    file.rewind()?;
+
+    let metadata_file = FileMetadata {
+        filename: filename.to_str().unwrap().to_string(),
+        path: path_str.to_string(),
+        file_size,
+        index_creation_time,
+        first_1000_bytes,
+        last_write_time,
+    };
+    Ok((file, metadata_file, sample_file))
+}
+
+impl BgenStream<Box<dyn Read>> {
+    /// Opens a bgen file like [`BgenStream::from_path`], `-` stands for the standard input
+    pub fn from_input(
+        path_str: &str,
+        use_sample_file: bool,
+        read_data_block: bool,
+    ) -> Result<Self> {
+        if path_str == "-" {
+            return Ok(BgenStream::from_reader(
+                Box::new(io::stdin().lock()),
+                read_data_block,
+            ));
+        }
+        let (file, metadata_file, sample_file) = open_file(path_str, use_sample_file)?;
         Ok(BgenStream::new(
-            stream,
+            BufReader::new(Box::new(file)),
             MetadataBgi::File(metadata_file),
-            samples,
+            sample_file,
             read_data_block,
         ))
     }
 }
 
+impl BgenClone<Box<dyn Read>> for BgenStream<Box<dyn Read>> {
+    fn create_identical_bgen(&self) -> Result<BgenStream<Box<dyn Read>>> {
+        let mut new_bgen = match self.metadata.clone() {
+            MetadataBgi::File(file_meta) => BgenStream::from_input(&file_meta.path, false, true),
+            _ => Err(Report::msg("Bgen read from a stream can only be read once")),
+        }?;
+        new_bgen.ranges.clone_from(&self.ranges);
+        new_bgen.chr_renamer.clone_from(&self.chr_renamer);
+        new_bgen.chunk.clone_from(&self.chunk);
+        Ok(new_bgen)
+    }
+}
+
 impl BgenClone<File> for BgenStream<File> {
     fn create_identical_bgen(&self) -> Result<BgenStream<File>> {
         let mut new_bgen = match self.metadata.clone() {
@@ -540,6 +989,8 @@ impl BgenClone<File> for BgenStream<File> {
             )),
         }?;
         new_bgen.ranges.clone_from(&self.ranges);
+        new_bgen.chr_renamer.clone_from(&self.chr_renamer);
+        new_bgen.chunk.clone_from(&self.chunk);
         Ok(new_bgen)
     }
 }
@@ -558,11 +1009,13 @@ impl BgenClone<Cursor<Vec<u8>>> for BgenStream<Cursor<Vec<u8>>> {
     fn create_identical_bgen(&self) -> Result<BgenStream<Cursor<Vec<u8>>>> {
         let mut new_bgen = match self.metadata.clone() {
             MetadataBgi::Bytes(meta_bytes) => BgenStream::from_bytes(meta_bytes.bytes, true),
-            MetadataBgi::File(_) => Err(Report::msg(
-                "No bytes metadata in bgen constructed from file",
+            _ => Err(Report::msg(
+                "No bytes metadata in bgen constructed from bytes",
             )),
         }?;
         new_bgen.ranges.clone_from(&self.ranges);
+        new_bgen.chr_renamer.clone_from(&self.chr_renamer);
+        new_bgen.chunk.clone_from(&self.chunk);
         Ok(new_bgen)
     }
 }
@@ -1,9 +1,11 @@
-use crate::bgen::bgen_stream::FileMetadata;
+use crate::bgen::bgen_stream::{BgenStream, FileMetadata, MetadataBgi};
 use crate::bgen::variant_data::VariantData;
-use color_eyre::Result;
+use color_eyre::{Report, Result};
 use itertools::Itertools;
 use sqlite::Connection;
-use sqlite::Value;
+use sqlite::{State, Value};
+use std::fs::File;
+use std::time::SystemTime;
 
 static VARIANT_CREATION_STRING: &str = r#"CREATE TABLE Variant (
   chromosome TEXT NOT NULL,
@@ -25,6 +27,60 @@ static METADATA_CREATION_STRING: &str = r#"CREATE TABLE Metadata (
   index_creation_time INT NOT NULL
 );"#;
 
+/// Extensions of the indexes of a bgen file, written by this crate or by bgenix
+pub const INDEX_EXTENSIONS: [&str; 2] = [".bgi_rust", ".bgi"];
+
+/// Writes the index of the file read by the stream, whose header has been read, in a
+/// `.bgi_rust` file next to it. Returns the name of the index.
+pub fn write_index(bgen_stream: BgenStream<File>) -> Result<String> {
+    let file_metadata = file_metadata(&bgen_stream)?;
+    let bgi_filename = file_metadata.path.to_string() + INDEX_EXTENSIONS[0];
+    let table_creator = TableCreator::new(bgi_filename.clone())?;
+    table_creator.init(&file_metadata)?;
+    table_creator.store(bgen_stream)?;
+    Ok(bgi_filename)
+}
+
+/// Adds the variants of the stream to the existing index at `index_path` of the file it
+/// reads, and refreshes the file metadata stored in the index
+pub fn update_index(bgen_stream: BgenStream<File>, index_path: &str) -> Result<()> {
+    let file_metadata = file_metadata(&bgen_stream)?;
+    let table_creator = TableCreator::new(index_path.to_string())?;
+    table_creator.update_metadata(&file_metadata)?;
+    table_creator.store(bgen_stream)?;
+    Ok(())
+}
+
+/// Tells whether the file size and last write time stored in the index at `index_path`
+/// are those of the bgen file, that is whether the file was not written after the index
+pub fn index_is_current(bgen_path: &str, index_path: &str) -> Result<bool> {
+    let metadata = std::fs::metadata(bgen_path)?;
+    let conn = sqlite::open(index_path)?;
+    let mut statement = conn.prepare("SELECT file_size, last_write_time FROM Metadata")?;
+    if statement.next()? != State::Row {
+        return Ok(false);
+    }
+    Ok(
+        statement.read::<i64, _>("file_size")? == metadata.len() as i64
+            && statement.read::<i64, _>("last_write_time")? == unix_seconds(metadata.modified()?),
+    )
+}
+
+/// Seconds since the Unix epoch, as bgenix stores times in the index
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+fn file_metadata(bgen_stream: &BgenStream<File>) -> Result<FileMetadata> {
+    match &bgen_stream.metadata {
+        MetadataBgi::File(file_metadata) => Ok(file_metadata.clone()),
+        _ => Err(Report::msg(
+            "Only bgen files opened from a path can be indexed",
+        )),
+    }
+}
+
 pub struct TableCreator {
     conn: Connection,
 }
@@ -55,11 +111,27 @@ impl TableCreator {
             &[
                 Value::String(meta.filename.clone()),
                 Value::Integer(meta.file_size as i64),
-                Value::Integer(meta.last_write_time.elapsed().unwrap().as_secs() as i64),
+                Value::Integer(unix_seconds(meta.last_write_time)),
+                Value::Binary(meta.first_1000_bytes.clone()),
+                Value::Integer(unix_seconds(meta.index_creation_time)),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    pub fn update_metadata(&self, meta: &FileMetadata) -> Result<()> {
+        let query =
+            "UPDATE Metadata SET file_size = ?1, last_write_time = ?2, first_1000_bytes = ?3";
+        let mut statement = self.conn.prepare(query)?;
+        statement.bind(
+            &[
+                Value::Integer(meta.file_size as i64),
+                Value::Integer(unix_seconds(meta.last_write_time)),
                 Value::Binary(meta.first_1000_bytes.clone()),
-                Value::Integer(meta.index_creation_time.elapsed().unwrap().as_secs() as i64),
             ][..],
         )?;
+        statement.next()?;
         Ok(())
     }
 
@@ -86,6 +158,8 @@ impl TableCreator {
                                 Some(Value::Integer(var_data.size_in_bytes as i64)),
                             ][..],
                         )?;
+                        statement.next()?;
+                        statement.reset()?;
                         Ok(())
                     })
                     .collect::<Result<Vec<_>>>()
@@ -0,0 +1,155 @@
+use crate::bgen::utils::read_two_column_mapping;
+use clap::ValueEnum;
+use color_eyre::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Name used to compare chromosomes across naming conventions: no `chr` prefix, no
+/// leading zeros, and X, Y, XY and MT instead of 23, 24, 25 and 26
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::bgen::chromosome::canonical_chr;
+/// assert_eq!(canonical_chr("chr1"), "1");
+/// assert_eq!(canonical_chr("01"), "1");
+/// assert_eq!(canonical_chr("23"), "X");
+/// assert_eq!(canonical_chr("chrM"), "MT");
+/// assert_eq!(canonical_chr("GL000192.1"), "GL000192.1");
+/// ```
+pub fn canonical_chr(chr: &str) -> &str {
+    let stripped = strip_chr_prefix(chr);
+    let trimmed = match stripped.trim_start_matches('0') {
+        "" if !stripped.is_empty() => "0",
+        trimmed => trimmed,
+    };
+    match trimmed {
+        "23" => "X",
+        "24" => "Y",
+        "25" => "XY",
+        "26" => "MT",
+        t if t.eq_ignore_ascii_case("x") => "X",
+        t if t.eq_ignore_ascii_case("y") => "Y",
+        t if t.eq_ignore_ascii_case("xy") => "XY",
+        t if t.eq_ignore_ascii_case("m") || t.eq_ignore_ascii_case("mt") => "MT",
+        t => t,
+    }
+}
+
+fn strip_chr_prefix(chr: &str) -> &str {
+    match chr.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("chr") => &chr[3..],
+        _ => chr,
+    }
+}
+
+/// Order of the chromosomes when sorting variants
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ChrOrder {
+    /// 1 to 22, then X, Y, XY, MT and other names in lexicographic order
+    #[default]
+    Natural,
+    /// Chromosomes in the given order, then names missing from it in lexicographic order
+    Custom(Vec<String>),
+}
+
+impl ChrOrder {
+    /// Parses a comma separated list of chromosomes, such as `1,2,3,X`
+    pub fn from_list(chr_list: &str) -> Self {
+        ChrOrder::Custom(
+            chr_list
+                .split(',')
+                .map(|c| canonical_chr(c.trim()).to_string())
+                .filter(|c| !c.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Key used to compare chromosomes, names are compared after the rank
+    ///
+    /// # Examples
+    /// ```
+    /// # use bgen_reader::bgen::chromosome::ChrOrder;
+    /// let order = ChrOrder::Natural;
+    /// assert!(order.rank("2") < order.rank("10"));
+    /// assert!(order.rank("chr22") < order.rank("X"));
+    /// let order = ChrOrder::from_list("X,1");
+    /// assert!(order.rank("23") < order.rank("1"));
+    /// ```
+    pub fn rank(&self, chr: &str) -> (usize, String) {
+        let canonical = canonical_chr(chr);
+        let rank = match self {
+            ChrOrder::Natural => match canonical {
+                "X" => Some(23),
+                "Y" => Some(24),
+                "XY" => Some(25),
+                "MT" => Some(26),
+                c => c.parse::<usize>().ok(),
+            },
+            ChrOrder::Custom(chr_list) => chr_list.iter().position(|c| c == canonical),
+        };
+        match rank {
+            Some(rank) => (rank, String::new()),
+            None => (usize::MAX, canonical.to_string()),
+        }
+    }
+}
+
+/// Chromosome naming conventions that can be used on output
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChrConvention {
+    /// chr1, ..., chrX, chrY, chrM
+    Ucsc,
+    /// 1, ..., X, Y, MT
+    Ensembl,
+    /// 1, ..., 23, 24, 25, 26
+    Numeric,
+}
+
+/// Rewrites chromosome names, either to a naming convention or with a user mapping
+#[derive(Clone, Debug)]
+pub enum ChrRenamer {
+    Convention(ChrConvention),
+    Mapping(HashMap<String, String>),
+}
+
+impl ChrRenamer {
+    /// Reads a mapping file with the old and the new name of a chromosome on each line
+    pub fn from_mapping_file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mapping = read_two_column_mapping(path, "chromosome mapping")?;
+        Ok(ChrRenamer::Mapping(mapping))
+    }
+
+    /// Chromosomes missing from a mapping are left unchanged
+    ///
+    /// # Examples
+    /// ```
+    /// # use bgen_reader::bgen::chromosome::{ChrConvention, ChrRenamer};
+    /// let renamer = ChrRenamer::Convention(ChrConvention::Ucsc);
+    /// assert_eq!(renamer.rename("01"), "chr1");
+    /// assert_eq!(renamer.rename("26"), "chrM");
+    /// let renamer = ChrRenamer::Convention(ChrConvention::Numeric);
+    /// assert_eq!(renamer.rename("chrX"), "23");
+    /// ```
+    pub fn rename(&self, chr: &str) -> String {
+        match self {
+            ChrRenamer::Mapping(mapping) => mapping.get(chr).map_or(chr, |c| c.as_str()),
+            ChrRenamer::Convention(convention) => {
+                let canonical = canonical_chr(chr);
+                match (convention, canonical) {
+                    (ChrConvention::Ucsc, "MT") => return "chrM".to_string(),
+                    (ChrConvention::Ucsc, canonical) => return format!("chr{}", canonical),
+                    (ChrConvention::Ensembl, canonical) => canonical,
+                    (ChrConvention::Numeric, "X") => "23",
+                    (ChrConvention::Numeric, "Y") => "24",
+                    (ChrConvention::Numeric, "XY") => "25",
+                    (ChrConvention::Numeric, "MT") => "26",
+                    (ChrConvention::Numeric, canonical) => canonical,
+                }
+            }
+        }
+        .to_string()
+    }
+}
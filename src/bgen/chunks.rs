@@ -0,0 +1,208 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::bgi_writer::{index_is_current, INDEX_EXTENSIONS};
+use color_eyre::{Report, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Consecutive variants of a file, from the byte at `start_offset` up to `end_offset`
+/// excluded. Chunks never split a variant.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chunk {
+    pub start_offset: u64,
+    pub end_offset: u64,
+    pub num_variants: u32,
+    pub first_chr: String,
+    pub first_pos: u32,
+    pub last_chr: String,
+    pub last_pos: u32,
+}
+
+impl Chunk {
+    /// Region covered by a chunk on a single chromosome of a position sorted file, in the
+    /// syntax of range filters
+    pub fn region(&self) -> Option<String> {
+        (self.first_chr == self.last_chr)
+            .then(|| format!("{}:{}-{}", self.first_chr, self.first_pos, self.last_pos))
+    }
+}
+
+/// Location of a variant in the file, as stored in the index
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantLocation {
+    pub chr: String,
+    pub pos: u32,
+    pub file_start_position: u64,
+    pub size_in_bytes: u64,
+}
+
+/// Limit on the content of each chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkSize {
+    Variants(usize),
+    Bytes(u64),
+}
+
+/// Reads the variant locations from the `.bgi_rust` or `.bgi` index next to the file, or
+/// from a scan of the variant identifying blocks if there is no index. Indexes whose file
+/// size or last write time differ from those of the file are out of date and ignored, as
+/// are indexes without any variant.
+pub fn read_locations(bgen_path: &str) -> Result<Vec<VariantLocation>> {
+    for extension in INDEX_EXTENSIONS {
+        let index_path = format!("{}{}", bgen_path, extension);
+        if !Path::new(&index_path).exists() {
+            continue;
+        }
+        if !index_is_current(bgen_path, &index_path)? {
+            log::warn!("{} is out of date, it is ignored", index_path);
+            continue;
+        }
+        log::info!("Reading variant locations from {}", index_path);
+        let locations = read_locations_from_index(&index_path)?;
+        // older versions wrote indexes without any variant
+        if locations.is_empty() {
+            log::warn!("{} has no variants, it is ignored", index_path);
+            continue;
+        }
+        return Ok(locations);
+    }
+    log::info!("No index found, scanning {}", bgen_path);
+    let mut bgen_stream = BgenStream::from_path(bgen_path, false, false)?;
+    bgen_stream.read_offset_and_header()?;
+    bgen_stream
+        .map(|variant_data| {
+            let variant_data = variant_data?;
+            Ok(VariantLocation {
+                chr: variant_data.chr,
+                pos: variant_data.pos,
+                file_start_position: variant_data.file_start_position as u64,
+                size_in_bytes: variant_data.size_in_bytes as u64,
+            })
+        })
+        .collect()
+}
+
+fn read_locations_from_index(index_path: &str) -> Result<Vec<VariantLocation>> {
+    let conn = sqlite::open(index_path)?;
+    let query = "SELECT chromosome, position, file_start_position, size_in_bytes FROM Variant \
+                 ORDER BY file_start_position";
+    let locations = conn
+        .prepare(query)?
+        .into_iter()
+        .map(|row| {
+            let row = row?;
+            Ok(VariantLocation {
+                chr: row.try_read::<&str, _>("chromosome")?.to_string(),
+                pos: row.try_read::<i64, _>("position")?.try_into()?,
+                file_start_position: row.try_read::<i64, _>("file_start_position")?.try_into()?,
+                size_in_bytes: row.try_read::<i64, _>("size_in_bytes")?.try_into()?,
+            })
+        })
+        .collect();
+    locations
+}
+
+/// Groups consecutive variants in chunks holding at most `chunk_size` variants or bytes.
+/// A variant larger than the byte limit gets a chunk of its own. With `by_chromosome`,
+/// chunks never span two chromosomes, so that each one covers a single region.
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::bgen::chunks::{plan_chunks, ChunkSize, VariantLocation};
+/// let locations: Vec<_> = [("1", 10), ("1", 20), ("2", 5)]
+///     .iter()
+///     .enumerate()
+///     .map(|(i, (chr, pos))| VariantLocation {
+///         chr: chr.to_string(),
+///         pos: *pos,
+///         file_start_position: 100 + 10 * i as u64,
+///         size_in_bytes: 10,
+///     })
+///     .collect();
+/// let chunks = plan_chunks(&locations, ChunkSize::Bytes(25), false);
+/// assert_eq!(2, chunks.len());
+/// assert_eq!((100, 120), (chunks[0].start_offset, chunks[0].end_offset));
+/// let chunks = plan_chunks(&locations, ChunkSize::Variants(5), true);
+/// assert_eq!(Some("1:10-20".to_string()), chunks[0].region());
+/// assert_eq!(Some("2:5-5".to_string()), chunks[1].region());
+/// ```
+pub fn plan_chunks(
+    locations: &[VariantLocation],
+    chunk_size: ChunkSize,
+    by_chromosome: bool,
+) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut current: Option<Chunk> = None;
+    for location in locations {
+        let end_offset = location.file_start_position + location.size_in_bytes;
+        if let Some(chunk) = current.as_mut() {
+            let full = match chunk_size {
+                ChunkSize::Variants(max_variants) => chunk.num_variants as usize >= max_variants,
+                ChunkSize::Bytes(max_bytes) => end_offset - chunk.start_offset > max_bytes,
+            };
+            if full || (by_chromosome && chunk.last_chr != location.chr) {
+                chunks.extend(current.take());
+            }
+        }
+        let chunk = current.get_or_insert_with(|| Chunk {
+            start_offset: location.file_start_position,
+            first_chr: location.chr.clone(),
+            first_pos: location.pos,
+            ..Default::default()
+        });
+        chunk.end_offset = end_offset;
+        chunk.num_variants += 1;
+        chunk.last_chr.clone_from(&location.chr);
+        chunk.last_pos = location.pos;
+    }
+    chunks.extend(current);
+    chunks
+}
+
+/// Opens a stream over the variants of a chunk only, the file is read from the start of
+/// the chunk
+pub fn open_chunk(
+    bgen_path: &str,
+    chunk: &Chunk,
+    read_data_block: bool,
+) -> Result<BgenStream<File>> {
+    let mut bgen_stream = BgenStream::from_path(bgen_path, false, read_data_block)?;
+    bgen_stream.read_offset_and_header()?;
+    bgen_stream.restrict_to_chunk(chunk.clone())?;
+    Ok(bgen_stream)
+}
+
+/// Writes one region per line with `regions`, otherwise a TSV line with the offsets,
+/// number of variants and bounds of each chunk
+pub fn write_chunks(mut writer: impl Write, chunks: &[Chunk], regions: bool) -> Result<()> {
+    if regions {
+        for chunk in chunks {
+            let region = chunk.region().ok_or_else(|| {
+                Report::msg("Chunks spanning several chromosomes have no single region")
+            })?;
+            writeln!(writer, "{}", region)?;
+        }
+        return Ok(());
+    }
+    writeln!(
+        writer,
+        "chunk\tstart_offset\tend_offset\tnumber_of_variants\tfirst_chromosome\tfirst_position\tlast_chromosome\tlast_position"
+    )?;
+    for (i, chunk) in chunks.iter().enumerate() {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            i + 1,
+            chunk.start_offset,
+            chunk.end_offset,
+            chunk.num_variants,
+            chunk.first_chr,
+            chunk.first_pos,
+            chunk.last_chr,
+            chunk.last_pos
+        )?;
+    }
+    Ok(())
+}
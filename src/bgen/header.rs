@@ -1,10 +1,9 @@
 use crate::bgen::utils::write_u32;
 use color_eyre::Result;
-use std::fs::File;
-use std::io::BufWriter;
 use std::io::Write;
 
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub start_data_offset: u32,
     pub header_size: u32,
@@ -12,21 +11,38 @@ pub struct Header {
     pub variant_count: u32,
     pub sample_num: u32,
     pub header_flags: HeaderFlags,
+    /// Bytes between the magic number and the flags, free for any use
+    pub free_data: Vec<u8>,
 }
 
+/// Size of a header block without free data
+pub const HEADER_SIZE_WITHOUT_FREE_DATA: u32 = 20;
+
+/// Offset of the number of variants in a bgen file, after the offset of the first variant
+/// and the size of the header
+pub const VARIANT_NUM_OFFSET: u64 = 8;
+
 impl Header {
-    pub fn write_header(&self, writer: &mut BufWriter<File>) -> Result<()> {
+    /// Replaces the free data area, and updates the header size accordingly
+    pub fn set_free_data(&mut self, free_data: Vec<u8>) {
+        self.header_size = HEADER_SIZE_WITHOUT_FREE_DATA + free_data.len() as u32;
+        self.free_data = free_data;
+    }
+
+    pub fn write_header(&self, writer: &mut impl Write) -> Result<()> {
         write_u32(writer, self.start_data_offset)?;
         write_u32(writer, self.header_size)?;
         write_u32(writer, self.variant_num)?;
         write_u32(writer, self.sample_num)?;
         writer.write_all(b"bgen")?;
+        writer.write_all(&self.free_data)?;
         write_u32(writer, self.header_flags.to_u32())?;
         Ok(())
     }
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HeaderFlags {
     pub compressed_snp_blocks: bool,
     pub layout_id: u8,
@@ -0,0 +1,127 @@
+use crate::bgen::bgen_stream::BgenStream;
+use clap::ValueEnum;
+use color_eyre::Result;
+use serde::Serialize;
+use std::io::{Read, Write};
+
+/// Format of the output of `info`
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InfoFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Content of a bgen file: its header, its samples and its variants per chromosome
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct BgenInfo {
+    pub start_data_offset: u32,
+    pub header_size: u32,
+    pub variant_num: u32,
+    pub sample_num: u32,
+    pub layout_id: u8,
+    pub compressed: bool,
+    pub sample_ids_present: bool,
+    pub free_data_size: usize,
+    /// Listed only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub samples: Option<Vec<String>>,
+    pub chromosomes: Vec<ChromosomeInfo>,
+}
+
+/// Variants of a chromosome, chromosomes are given in the order of the file
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct ChromosomeInfo {
+    pub chr: String,
+    pub num_variants: u32,
+    pub min_pos: u32,
+    pub max_pos: u32,
+}
+
+impl BgenInfo {
+    /// Reads the variants of the stream, whose header has been read, without decoding
+    /// their data blocks
+    pub fn from_stream<T: Read>(bgen_stream: BgenStream<T>, with_samples: bool) -> Result<Self> {
+        let header = bgen_stream.header.clone();
+        let samples = with_samples.then(|| bgen_stream.samples.clone());
+        let mut chromosomes: Vec<ChromosomeInfo> = Vec::new();
+        for variant_data in bgen_stream {
+            let variant_data = variant_data?;
+            match chromosomes.iter_mut().find(|c| c.chr == variant_data.chr) {
+                Some(chromosome) => {
+                    chromosome.num_variants += 1;
+                    chromosome.min_pos = chromosome.min_pos.min(variant_data.pos);
+                    chromosome.max_pos = chromosome.max_pos.max(variant_data.pos);
+                }
+                None => chromosomes.push(ChromosomeInfo {
+                    chr: variant_data.chr,
+                    num_variants: 1,
+                    min_pos: variant_data.pos,
+                    max_pos: variant_data.pos,
+                }),
+            }
+        }
+        Ok(BgenInfo {
+            start_data_offset: header.start_data_offset,
+            header_size: header.header_size,
+            variant_num: header.variant_num,
+            sample_num: header.sample_num,
+            layout_id: header.header_flags.layout_id,
+            compressed: header.header_flags.compressed_snp_blocks,
+            sample_ids_present: header.header_flags.sample_id_present,
+            free_data_size: header.free_data.len(),
+            samples,
+            chromosomes,
+        })
+    }
+
+    pub fn write(&self, mut writer: impl Write, format: InfoFormat) -> Result<()> {
+        match format {
+            InfoFormat::Text => self.write_text(writer),
+            InfoFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writer.write_all(b"\n")?;
+                Ok(())
+            }
+        }
+    }
+
+    fn write_text(&self, mut writer: impl Write) -> Result<()> {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        writeln!(
+            writer,
+            "Offset of the first variant: {}",
+            self.start_data_offset
+        )?;
+        writeln!(writer, "Header size: {}", self.header_size)?;
+        writeln!(writer, "Number of variants: {}", self.variant_num)?;
+        writeln!(writer, "Number of samples: {}", self.sample_num)?;
+        writeln!(writer, "Layout: {}", self.layout_id)?;
+        writeln!(writer, "Compressed: {}", yes_no(self.compressed))?;
+        writeln!(
+            writer,
+            "Sample identifiers: {}",
+            yes_no(self.sample_ids_present)
+        )?;
+        writeln!(writer, "Free data: {} bytes", self.free_data_size)?;
+        writeln!(writer)?;
+        writeln!(writer, "chromosome\tvariants\tmin_position\tmax_position")?;
+        for chromosome in &self.chromosomes {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}",
+                chromosome.chr, chromosome.num_variants, chromosome.min_pos, chromosome.max_pos
+            )?;
+        }
+        if let Some(samples) = &self.samples {
+            writeln!(writer)?;
+            writeln!(writer, "samples")?;
+            for sample in samples {
+                writeln!(writer, "{}", sample)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,20 @@
+pub mod append;
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
+#[cfg(feature = "ndarray")]
+pub mod batch;
 pub mod bgen_stream;
 pub mod bgi_writer;
+pub mod chromosome;
+pub mod chunks;
 pub mod header;
+pub mod info;
+pub mod range_set;
+pub mod reheader;
+pub mod samples;
+pub mod sort;
+pub mod split;
+pub mod stats;
 pub mod utils;
 pub mod variant_data;
+pub mod writer;
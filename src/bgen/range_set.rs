@@ -0,0 +1,83 @@
+use crate::bgen::chromosome::canonical_chr;
+use crate::parser::Range;
+use std::collections::{HashMap, HashSet};
+
+/// Ranges grouped by chromosome, sorted and merged so that a position is looked up with a
+/// binary search instead of a scan of every range. Chromosomes are compared on their
+/// canonical name, so that `chr1` matches `1` and `23` matches `X`.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct RangeSet {
+    by_chr: HashMap<String, Vec<(u32, u32)>>,
+}
+
+impl RangeSet {
+    pub fn new(ranges: &[Range]) -> Self {
+        let mut by_chr: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+        for range in ranges {
+            by_chr
+                .entry(canonical_chr(&range.chr).to_string())
+                .or_default()
+                .push((range.start, range.end));
+        }
+        for intervals in by_chr.values_mut() {
+            intervals.sort_unstable();
+            let mut merged: Vec<(u32, u32)> = Vec::with_capacity(intervals.len());
+            for &(start, end) in intervals.iter() {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *intervals = merged;
+        }
+        RangeSet { by_chr }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_chr.is_empty()
+    }
+
+    pub fn contains(&self, chr: &str, pos: u32) -> bool {
+        self.by_chr
+            .get(canonical_chr(chr))
+            .is_some_and(|intervals| {
+                let idx = intervals.partition_point(|&(start, _)| start <= pos);
+                idx > 0 && pos <= intervals[idx - 1].1
+            })
+    }
+
+    /// End of the last range on the chromosome
+    pub fn last_end(&self, chr: &str) -> Option<u32> {
+        self.by_chr
+            .get(canonical_chr(chr))
+            .and_then(|intervals| intervals.last().map(|&(_, end)| end))
+    }
+
+    pub fn chromosomes(&self) -> impl Iterator<Item = &String> {
+        self.by_chr.keys()
+    }
+}
+
+/// Tracks which chromosomes of a `RangeSet` a position sorted stream has gone past
+#[derive(Clone, Default, Debug)]
+pub struct RangeProgress {
+    current_chr: Option<String>,
+    passed: HashSet<String>,
+}
+
+impl RangeProgress {
+    /// Records the position of the last variant read and tells whether no later variant
+    /// can fall in `range_set`
+    pub fn passed_all(&mut self, range_set: &RangeSet, chr: &str, pos: u32) -> bool {
+        let chr = canonical_chr(chr);
+        if self.current_chr.as_deref() != Some(chr) {
+            if let Some(previous) = self.current_chr.replace(chr.to_string()) {
+                self.passed.insert(previous);
+            }
+        }
+        if range_set.last_end(chr).is_some_and(|end| pos > end) {
+            self.passed.insert(chr.to_string());
+        }
+        range_set.chromosomes().all(|c| self.passed.contains(c))
+    }
+}
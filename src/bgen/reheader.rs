@@ -0,0 +1,98 @@
+use crate::bgen::bgen_stream::{write_header_and_samples, BgenStream};
+use crate::bgen::header::Header;
+use color_eyre::{Report, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+/// Change made to the sample identifiers of a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SampleRewrite {
+    /// Replace each embedded identifier by its new name, every identifier must be mapped
+    Mapping(HashMap<String, String>),
+    /// Replace all the identifiers, or add them to a file that has none
+    Replace(Vec<String>),
+    /// Remove the embedded identifiers
+    Remove,
+}
+
+impl SampleRewrite {
+    /// New identifiers of the samples, empty when they are removed
+    pub fn apply(&self, samples: &[String], sample_num: u32) -> Result<Vec<String>> {
+        match self {
+            SampleRewrite::Mapping(mapping) => {
+                if samples.is_empty() {
+                    return Err(Report::msg(
+                        "No sample identifiers embedded in the bgen file to map",
+                    ));
+                }
+                samples
+                    .iter()
+                    .map(|sample| {
+                        mapping.get(sample).cloned().ok_or_else(|| {
+                            Report::msg(format!("Sample {} is missing from the mapping", sample))
+                        })
+                    })
+                    .collect()
+            }
+            SampleRewrite::Replace(new_samples) => {
+                if new_samples.len() != sample_num as usize {
+                    return Err(Report::msg(format!(
+                        "Bgen file has {} samples but {} new identifiers were given",
+                        sample_num,
+                        new_samples.len()
+                    )));
+                }
+                Ok(new_samples.clone())
+            }
+            SampleRewrite::Remove => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Writes a copy of `input_path` with the sample identifier block rewritten. The offset of
+/// the first variant is recomputed, and the variant blocks are copied without decoding them.
+pub fn reheader_bgen(input_path: &str, output_path: &str, rewrite: &SampleRewrite) -> Result<()> {
+    let mut bgen_stream = BgenStream::from_path(input_path, false, false)?;
+    bgen_stream.read_offset_and_header()?;
+    let header = bgen_stream.header.clone();
+    let samples = rewrite.apply(&bgen_stream.samples, header.sample_num)?;
+    let no_samples = *rewrite == SampleRewrite::Remove;
+    copy_with_header(input_path, output_path, &header, &samples, no_samples)
+}
+
+/// Writes a copy of `input_path` with `free_data` in the free data area of the header,
+/// the variant blocks are copied without decoding them
+pub fn set_free_data(input_path: &str, output_path: &str, free_data: Vec<u8>) -> Result<()> {
+    let mut bgen_stream = BgenStream::from_path(input_path, false, false)?;
+    bgen_stream.read_offset_and_header()?;
+    let mut header = bgen_stream.header.clone();
+    header.set_free_data(free_data);
+    let no_samples = !header.header_flags.sample_id_present;
+    copy_with_header(
+        input_path,
+        output_path,
+        &header,
+        &bgen_stream.samples,
+        no_samples,
+    )
+}
+
+/// Writes `header` and the samples, then the variant blocks of `input_path` as they are.
+/// `header` is the header of `input_path`, its offset of the first variant is still the one
+/// of the input and is recomputed on output.
+fn copy_with_header(
+    input_path: &str,
+    output_path: &str,
+    header: &Header,
+    samples: &[String],
+    no_samples: bool,
+) -> Result<()> {
+    let mut input = File::open(input_path)?;
+    input.seek(SeekFrom::Start(header.start_data_offset as u64 + 4))?;
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    write_header_and_samples(&mut writer, header, samples, header.variant_num, no_samples)?;
+    io::copy(&mut input, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
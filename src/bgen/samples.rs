@@ -0,0 +1,119 @@
+use crate::bgen::utils::read_two_column_mapping;
+use clap::ValueEnum;
+use color_eyre::{Report, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Which sample identifiers to keep when both the bgen file and a .sample file provide them
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplePolicy {
+    /// Use the identifiers embedded in the bgen file
+    PreferEmbedded,
+    /// Use the ID_1 column of the .sample file
+    PreferSampleFile,
+    /// Fail unless the embedded identifiers and the ID_1 column are identical
+    #[default]
+    RequireMatch,
+    /// Fail unless the embedded identifiers and the ID_2 column are identical
+    MatchId2,
+}
+
+/// One line of a .sample file, after the two header lines
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SampleFileEntry {
+    pub id_1: String,
+    pub id_2: String,
+}
+
+pub fn read_sample_file<P>(path: P) -> Result<Vec<SampleFileEntry>>
+where
+    P: AsRef<Path>,
+{
+    let file = File::open(path)?;
+    BufReader::new(file)
+        .lines()
+        .skip(2)
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line?;
+            let mut columns = line.split_whitespace();
+            match (columns.next(), columns.next()) {
+                (Some(id_1), Some(id_2)) => Ok(SampleFileEntry {
+                    id_1: id_1.to_string(),
+                    id_2: id_2.to_string(),
+                }),
+                _ => Err(Report::msg(format!(
+                    "Line {} of .sample file does not contain ID_1 and ID_2 columns",
+                    i + 3
+                ))),
+            }
+        })
+        .collect()
+}
+
+/// Reads a mapping file with the old and the new identifier of a sample on each line
+pub fn read_sample_mapping<P>(path: P) -> Result<HashMap<String, String>>
+where
+    P: AsRef<Path>,
+{
+    read_two_column_mapping(path, "sample mapping")
+}
+
+/// Picks the sample identifiers of a stream according to `policy`.
+/// `embedded` is `None` when the bgen file has no sample identifier block.
+pub fn resolve_samples(
+    embedded: Option<Vec<String>>,
+    sample_file: &[SampleFileEntry],
+    policy: SamplePolicy,
+) -> Result<Vec<String>> {
+    let id_1 = || sample_file.iter().map(|e| e.id_1.clone()).collect();
+    let embedded = match embedded {
+        Some(embedded) => embedded,
+        None => return Ok(id_1()),
+    };
+    if sample_file.is_empty() {
+        return Ok(embedded);
+    }
+    if embedded.len() != sample_file.len() {
+        return Err(Report::msg(format!(
+            "Bgen file embeds {} sample identifiers but the .sample file lists {}",
+            embedded.len(),
+            sample_file.len()
+        )));
+    }
+    match policy {
+        SamplePolicy::PreferEmbedded => Ok(embedded),
+        SamplePolicy::PreferSampleFile => Ok(id_1()),
+        SamplePolicy::RequireMatch => {
+            check_match(&embedded, sample_file.iter().map(|e| &e.id_1), "ID_1")?;
+            Ok(embedded)
+        }
+        SamplePolicy::MatchId2 => {
+            check_match(&embedded, sample_file.iter().map(|e| &e.id_2), "ID_2")?;
+            Ok(embedded)
+        }
+    }
+}
+
+fn check_match<'a>(
+    embedded: &[String],
+    from_file: impl Iterator<Item = &'a String>,
+    column: &str,
+) -> Result<()> {
+    match embedded
+        .iter()
+        .zip(from_file)
+        .enumerate()
+        .find(|(_, (e, f))| e != f)
+    {
+        Some((i, (e, f))) => Err(Report::msg(format!(
+            "Samples embedded in bgen file and in .sample file do not match: \
+            sample {} is {} in the bgen file but {} in the {} column of the .sample file",
+            i, e, f, column
+        ))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,157 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::chromosome::ChrOrder;
+use crate::bgen::variant_data::VariantData;
+use color_eyre::{Report, Result};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// Position of a variant in the sort order, and where its bytes are in the file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SortRecord {
+    chr_rank: (usize, String),
+    pos: u32,
+    alleles: Vec<String>,
+    file_start_position: u64,
+    size_in_bytes: u64,
+}
+
+impl SortRecord {
+    fn new(variant_data: &VariantData, chr_order: &ChrOrder) -> Self {
+        SortRecord {
+            chr_rank: chr_order.rank(&variant_data.chr),
+            pos: variant_data.pos,
+            alleles: variant_data.alleles.clone(),
+            file_start_position: variant_data.file_start_position as u64,
+            size_in_bytes: variant_data.size_in_bytes as u64,
+        }
+    }
+
+    /// Chromosome, position and alleles, what the variants are ordered by
+    fn sort_key(&self) -> (&(usize, String), u32, &[String]) {
+        (&self.chr_rank, self.pos, &self.alleles)
+    }
+
+    fn write_line(&self, mut writer: impl Write) -> Result<()> {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.chr_rank.0,
+            self.chr_rank.1,
+            self.pos,
+            self.file_start_position,
+            self.size_in_bytes,
+            self.alleles.join("\t")
+        )?;
+        Ok(())
+    }
+
+    fn from_line(line: &str) -> Result<Self> {
+        let err = || Report::msg(format!("Corrupted sort chunk line: {}", line));
+        let mut fields = line.split('\t');
+        let mut next = || fields.next().ok_or_else(err);
+        let rank = next()?.parse::<usize>()?;
+        let chr = next()?.to_string();
+        let pos = next()?.parse::<u32>()?;
+        let file_start_position = next()?.parse::<u64>()?;
+        let size_in_bytes = next()?.parse::<u64>()?;
+        Ok(SortRecord {
+            chr_rank: (rank, chr),
+            pos,
+            alleles: fields.map(|a| a.to_string()).collect(),
+            file_start_position,
+            size_in_bytes,
+        })
+    }
+}
+
+/// Tells whether the variants of the stream are ordered by chromosome, position and
+/// alleles. The stream is best created without reading the data blocks.
+pub fn is_sorted<T: Read>(bgen_stream: BgenStream<T>, chr_order: &ChrOrder) -> Result<bool> {
+    let mut previous: Option<SortRecord> = None;
+    for variant_data in bgen_stream {
+        let record = SortRecord::new(&variant_data?, chr_order);
+        if previous
+            .as_ref()
+            .is_some_and(|p| p.sort_key() > record.sort_key())
+        {
+            return Ok(false);
+        }
+        previous = Some(record);
+    }
+    Ok(true)
+}
+
+/// Writes the variants of `input_path` ordered by chromosome, position and alleles.
+/// At most `max_in_memory` variant positions are held in memory, the rest is sorted in
+/// chunk files next to the output which are merged afterwards. Variant blocks are copied
+/// from the input without being decoded.
+pub fn sort_bgen(
+    input_path: &str,
+    output_path: &str,
+    chr_order: &ChrOrder,
+    max_in_memory: usize,
+) -> Result<()> {
+    let mut bgen_stream = BgenStream::from_path(input_path, false, false)?;
+    bgen_stream.read_offset_and_header()?;
+    let start_data = bgen_stream.header.start_data_offset as u64 + 4;
+    let mut records = Vec::new();
+    let mut chunk_paths = Vec::new();
+    for variant_data in bgen_stream {
+        records.push(SortRecord::new(&variant_data?, chr_order));
+        if records.len() >= max_in_memory.max(1) {
+            let chunk_path = format!("{}.sort_chunk_{}", output_path, chunk_paths.len());
+            log::info!("Writing sorted chunk {}", chunk_path);
+            write_chunk(&mut records, &chunk_path)?;
+            chunk_paths.push(chunk_path);
+        }
+    }
+    records.sort_unstable();
+    let mut sources: Vec<Box<dyn Iterator<Item = Result<SortRecord>>>> =
+        vec![Box::new(records.into_iter().map(Ok))];
+    for chunk_path in chunk_paths.iter() {
+        let lines = BufReader::new(File::open(chunk_path)?).lines();
+        sources.push(Box::new(lines.map(|line| SortRecord::from_line(&line?))));
+    }
+
+    let mut input = File::open(input_path)?;
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    // header, free data area and samples are copied as is
+    io::copy(&mut Read::by_ref(&mut input).take(start_data), &mut writer)?;
+    let mut heap = BinaryHeap::new();
+    for (i, source) in sources.iter_mut().enumerate() {
+        if let Some(record) = source.next() {
+            heap.push(Reverse((record?, i)));
+        }
+    }
+    while let Some(Reverse((record, i))) = heap.pop() {
+        input.seek(SeekFrom::Start(record.file_start_position))?;
+        let copied = io::copy(
+            &mut Read::by_ref(&mut input).take(record.size_in_bytes),
+            &mut writer,
+        )?;
+        if copied != record.size_in_bytes {
+            return Err(Report::msg(format!(
+                "Variant at offset {} is truncated",
+                record.file_start_position
+            )));
+        }
+        if let Some(next) = sources[i].next() {
+            heap.push(Reverse((next?, i)));
+        }
+    }
+    writer.flush()?;
+    chunk_paths.iter().try_for_each(std::fs::remove_file)?;
+    Ok(())
+}
+
+fn write_chunk(records: &mut Vec<SortRecord>, chunk_path: &str) -> Result<()> {
+    records.sort_unstable();
+    let mut writer = BufWriter::new(File::create(chunk_path)?);
+    records
+        .drain(..)
+        .try_for_each(|record| record.write_line(&mut writer))?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::bgen::bgen_stream::{write_header_and_samples, BgenClone, BgenStream};
+use crate::bgen::bgi_writer::write_index;
+use clap::ValueEnum;
+use color_eyre::{Report, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+
+/// How the variants are distributed between the files of a split
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitMode {
+    /// One file per chromosome
+    #[default]
+    Chromosome,
+    /// Chunks with about the same number of variants
+    Variants,
+    /// Chunks with about the same size in bytes
+    Bytes,
+}
+
+/// Cuts a sequence of weights in at most `num_chunks` consecutive, non empty ranges whose
+/// total weights are as close as possible to each other
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::bgen::split::partition_by_weight;
+/// assert_eq!(partition_by_weight(&[1, 1, 1, 1], 2), vec![0..2, 2..4]);
+/// assert_eq!(partition_by_weight(&[3, 1, 1, 1], 2), vec![0..1, 1..4]);
+/// assert_eq!(partition_by_weight(&[1, 1], 3), vec![0..1, 1..2]);
+/// ```
+pub fn partition_by_weight(weights: &[u64], num_chunks: usize) -> Vec<Range<usize>> {
+    let total: u64 = weights.iter().sum();
+    let num_chunks = num_chunks.max(1) as u64;
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut cumulated = 0;
+    for (i, weight) in weights.iter().enumerate() {
+        cumulated += weight;
+        // a chunk ends once it reaches its share of the total weight
+        let chunk_end = total * (chunks.len() as u64 + 1) / num_chunks;
+        if cumulated >= chunk_end && (chunks.len() as u64) < num_chunks - 1 {
+            chunks.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    if start < weights.len() {
+        chunks.push(start..weights.len());
+    }
+    chunks
+}
+
+/// Splits the variants of the stream, whose header has been read, in several bgen files
+/// named after `output_prefix`: `{prefix}.{chromosome}.bgen` per chromosome, where characters
+/// that do not belong in a file name are replaced by `_`, or `{prefix}.chunk_{i}.bgen` with
+/// `i` starting at 1 for `num_chunks` chunks. Each file
+/// keeps the samples of the stream, and is indexed if `with_index` is set. Filters and
+/// chromosome renaming of the stream apply. Returns the names of the files written.
+pub fn split_bgen<T: Read>(
+    mut bgen_stream: BgenStream<T>,
+    output_prefix: &str,
+    split_mode: SplitMode,
+    num_chunks: usize,
+    with_index: bool,
+) -> Result<Vec<String>>
+where
+    BgenStream<T>: BgenClone<T>,
+{
+    let mut copy_stream = bgen_stream.create_identical_bgen()?;
+    copy_stream.read_offset_and_header()?;
+    let header = bgen_stream.header.clone();
+    let samples = std::mem::take(&mut bgen_stream.samples);
+    let layout_id = header.header_flags.layout_id;
+
+    // first pass to assign each variant to a file
+    let mut output_paths = Vec::new();
+    let mut variant_nums = Vec::new();
+    let mut piece_of_variant = Vec::new();
+    match split_mode {
+        SplitMode::Chromosome => {
+            let mut piece_of_chr: HashMap<String, usize> = HashMap::new();
+            for variant_data in bgen_stream {
+                let chr = variant_data?.chr;
+                let piece = match piece_of_chr.get(&chr) {
+                    Some(&piece) => piece,
+                    None => {
+                        let output_path =
+                            format!("{}.{}.bgen", output_prefix, file_name_part(&chr));
+                        if output_paths.contains(&output_path) {
+                            return Err(Report::msg(format!(
+                                "Chromosome {} would be written to {}, as another chromosome",
+                                chr, output_path
+                            )));
+                        }
+                        output_paths.push(output_path);
+                        variant_nums.push(0u32);
+                        piece_of_chr.insert(chr, output_paths.len() - 1);
+                        output_paths.len() - 1
+                    }
+                };
+                variant_nums[piece] += 1;
+                piece_of_variant.push(piece);
+            }
+        }
+        SplitMode::Variants | SplitMode::Bytes => {
+            let weights = bgen_stream
+                .map(|variant_data| match split_mode {
+                    SplitMode::Bytes => variant_data.map(|v| v.size_in_bytes as u64),
+                    _ => variant_data.map(|_| 1),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for (i, chunk) in partition_by_weight(&weights, num_chunks).iter().enumerate() {
+                output_paths.push(format!("{}.chunk_{}.bgen", output_prefix, i + 1));
+                variant_nums.push(chunk.len() as u32);
+                piece_of_variant.extend(chunk.clone().map(|_| i));
+            }
+        }
+    }
+
+    let mut writers = Vec::with_capacity(output_paths.len());
+    for (output_path, &variant_num) in output_paths.iter().zip(variant_nums.iter()) {
+        let mut writer = BufWriter::new(File::create(output_path)?);
+        write_header_and_samples(&mut writer, &header, &samples, variant_num, false)?;
+        writers.push(writer);
+    }
+    // second pass to copy the variants, without decoding them
+    let mut num_copied = 0;
+    while let Some(raw_variant) = copy_stream.next_raw_variant() {
+        let piece = *piece_of_variant.get(num_copied).ok_or_else(|| {
+            Report::msg("More variants were read than during the first pass of the split")
+        })?;
+        raw_variant?.write_self(&mut writers[piece], layout_id)?;
+        num_copied += 1;
+    }
+    if num_copied != piece_of_variant.len() {
+        return Err(Report::msg(format!(
+            "{} variants were copied by the split, out of {} read during the first pass",
+            num_copied,
+            piece_of_variant.len()
+        )));
+    }
+    writers.iter_mut().try_for_each(|writer| writer.flush())?;
+    if with_index {
+        for output_path in output_paths.iter() {
+            let mut piece_stream = BgenStream::from_path(output_path, false, false)?;
+            piece_stream.read_offset_and_header()?;
+            write_index(piece_stream)?;
+        }
+    }
+    Ok(output_paths)
+}
+
+/// Chromosome name usable in a file name, characters other than letters, digits, `-`, `_`
+/// and `.` are replaced by `_`
+fn file_name_part(chr: &str) -> String {
+    chr.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,251 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::utils::create_output;
+use crate::bgen::variant_data::VariantData;
+use color_eyre::Result;
+use std::io::{Read, Write};
+
+const STATS_HEADER: &[u8] = b"alternate_ids\trsid\tchromosome\tposition\talleleA\talleleB\t\
+HW_exact_p_value\talleleA_count\talleleB_count\talleleA_frequency\talleleB_frequency\t\
+minor_allele_frequency\tminor_allele\tmajor_allele\timpute_info\tmach_r2\t\
+missing_proportion\tAA\tAB\tBB\tNULL\ttotal\n";
+
+/// Per variant summary statistics, computed from the probabilities of the data block.
+/// Counts of the genotypes and the Hardy-Weinberg test only consider diploid samples.
+/// Statistics that are not defined for a variant (e.g. multiallelic) are NaN.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantStats {
+    pub allele_a_count: f64,
+    pub allele_b_count: f64,
+    pub allele_a_frequency: f64,
+    pub allele_b_frequency: f64,
+    pub minor_allele_frequency: f64,
+    pub minor_allele: Option<String>,
+    pub major_allele: Option<String>,
+    pub hwe_p_value: f64,
+    pub impute_info: f64,
+    pub mach_r2: f64,
+    pub missing_proportion: f64,
+    pub genotype_counts: [f64; 3],
+    pub missing_count: u32,
+    pub total: u32,
+}
+
+impl VariantStats {
+    pub fn from_variant(variant_data: &VariantData) -> Self {
+        let data_block = &variant_data.data_block;
+        let samples = data_block.sample_probabilities();
+        let total = samples.len() as u32;
+        let missing_count = samples.iter().filter(|s| s.missing).count() as u32;
+        let mut stats = VariantStats {
+            allele_a_count: f64::NAN,
+            allele_b_count: f64::NAN,
+            allele_a_frequency: f64::NAN,
+            allele_b_frequency: f64::NAN,
+            minor_allele_frequency: f64::NAN,
+            minor_allele: None,
+            major_allele: None,
+            hwe_p_value: f64::NAN,
+            impute_info: f64::NAN,
+            mach_r2: f64::NAN,
+            missing_proportion: missing_count as f64 / total as f64,
+            genotype_counts: [f64::NAN; 3],
+            missing_count,
+            total,
+        };
+        if data_block.number_alleles != 2 {
+            return stats;
+        }
+        let mut ploidy_sum = 0f64;
+        let mut dosage_sum = 0f64;
+        let mut dosage_sq_sum = 0f64;
+        let mut expected_sq_sum = 0f64;
+        let mut genotype_counts = [0f64; 3];
+        for sample in samples.iter().filter(|s| !s.missing) {
+            let (dosage, expected_sq, genotypes) = if data_block.phased {
+                let haplotypes: Vec<f64> = sample
+                    .probabilities
+                    .iter()
+                    .skip(1)
+                    .step_by(2)
+                    .copied()
+                    .collect();
+                let dosage: f64 = haplotypes.iter().sum();
+                let variance: f64 = haplotypes.iter().map(|q| q * (1f64 - q)).sum();
+                let genotypes = if let [q1, q2] = haplotypes[..] {
+                    let p_aa = (1f64 - q1) * (1f64 - q2);
+                    let p_bb = q1 * q2;
+                    Some([p_aa, 1f64 - p_aa - p_bb, p_bb])
+                } else {
+                    None
+                };
+                (dosage, variance + dosage * dosage, genotypes)
+            } else {
+                let moment = |power: i32| -> f64 {
+                    sample
+                        .probabilities
+                        .iter()
+                        .enumerate()
+                        .map(|(copies, p)| (copies as f64).powi(power) * p)
+                        .sum()
+                };
+                let genotypes = if let [p_aa, p_ab, p_bb] = sample.probabilities[..] {
+                    Some([p_aa, p_ab, p_bb])
+                } else {
+                    None
+                };
+                (moment(1), moment(2), genotypes)
+            };
+            ploidy_sum += sample.ploidy as f64;
+            dosage_sum += dosage;
+            dosage_sq_sum += dosage * dosage;
+            expected_sq_sum += expected_sq;
+            if let Some(genotypes) = genotypes {
+                genotype_counts
+                    .iter_mut()
+                    .zip(genotypes)
+                    .for_each(|(count, p)| *count += p);
+            }
+        }
+        let non_missing = (total - missing_count) as f64;
+        let theta = dosage_sum / ploidy_sum;
+        let allele_variance = theta * (1f64 - theta);
+        stats.allele_b_count = dosage_sum;
+        stats.allele_a_count = ploidy_sum - dosage_sum;
+        stats.allele_b_frequency = theta;
+        stats.allele_a_frequency = 1f64 - theta;
+        stats.genotype_counts = genotype_counts;
+        if ploidy_sum > 0f64 {
+            let (minor, major) = if theta <= 0.5 { (1, 0) } else { (0, 1) };
+            stats.minor_allele_frequency = theta.min(1f64 - theta);
+            stats.minor_allele = Some(variant_data.alleles[minor].clone());
+            stats.major_allele = Some(variant_data.alleles[major].clone());
+            // IMPUTE info measure, 1 by convention for monomorphic variants
+            stats.impute_info = if allele_variance > 0f64 {
+                1f64 - (expected_sq_sum - dosage_sq_sum) / (ploidy_sum * allele_variance)
+            } else {
+                1f64
+            };
+            // MaCH r2: observed variance of the dosages over the variance expected under HWE
+            if allele_variance > 0f64 {
+                let mean_dosage = dosage_sum / non_missing;
+                let dosage_variance = dosage_sq_sum / non_missing - mean_dosage * mean_dosage;
+                stats.mach_r2 = dosage_variance / (ploidy_sum / non_missing * allele_variance);
+            }
+            let [n_aa, n_ab, n_bb] = genotype_counts.map(|c| c.round() as u64);
+            stats.hwe_p_value = hwe_exact_p_value(n_ab, n_aa, n_bb);
+        }
+        stats
+    }
+
+    fn write_tsv_line(&self, variant_data: &VariantData, mut writer: impl Write) -> Result<()> {
+        let mut buffer = ryu::Buffer::new();
+        let mut format = |f: f64| -> String {
+            if f.is_nan() {
+                "NA".to_string()
+            } else {
+                buffer.format(f).to_string()
+            }
+        };
+        let allele = |i: usize| variant_data.alleles.get(i).map_or(".", |a| a.as_str());
+        let fields = [
+            variant_data.variants_id.clone(),
+            variant_data.rsid.clone(),
+            variant_data.chr.clone(),
+            variant_data.pos.to_string(),
+            allele(0).to_string(),
+            allele(1).to_string(),
+            format(self.hwe_p_value),
+            format(self.allele_a_count),
+            format(self.allele_b_count),
+            format(self.allele_a_frequency),
+            format(self.allele_b_frequency),
+            format(self.minor_allele_frequency),
+            self.minor_allele.clone().unwrap_or("NA".to_string()),
+            self.major_allele.clone().unwrap_or("NA".to_string()),
+            format(self.impute_info),
+            format(self.mach_r2),
+            format(self.missing_proportion),
+            format(self.genotype_counts[0]),
+            format(self.genotype_counts[1]),
+            format(self.genotype_counts[2]),
+            self.missing_count.to_string(),
+            self.total.to_string(),
+        ];
+        for (i, field) in fields.iter().enumerate() {
+            if i != 0 {
+                writer.write_all(b"\t")?;
+            }
+            if field.is_empty() {
+                writer.write_all(b".")?;
+            } else {
+                writer.write_all(field.as_bytes())?;
+            }
+        }
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Writes one line of statistics per variant of the stream, in a layout similar to
+/// `qctool -snp-stats`. `-` stands for the standard output.
+pub fn write_stats<T: Read>(output_path: &str, bgen_stream: BgenStream<T>) -> Result<()> {
+    let mut writer = create_output(output_path)?;
+    writer.write_all(STATS_HEADER)?;
+    bgen_stream.into_iter().try_for_each(|variant_data| {
+        let variant_data = variant_data?;
+        VariantStats::from_variant(&variant_data).write_tsv_line(&variant_data, &mut writer)
+    })?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Exact test of Hardy-Weinberg equilibrium, as described in
+/// Wigginton et al. 2005, "A Note on Exact Tests of Hardy-Weinberg Equilibrium"
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::bgen::stats::hwe_exact_p_value;
+/// assert_eq!(hwe_exact_p_value(50, 25, 25), 1f64);
+/// assert!(hwe_exact_p_value(0, 50, 50) < 1e-10);
+/// ```
+pub fn hwe_exact_p_value(n_ab: u64, n_aa: u64, n_bb: u64) -> f64 {
+    let n = (n_aa + n_ab + n_bb) as i64;
+    if n == 0 {
+        return f64::NAN;
+    }
+    let hom_rare = n_aa.min(n_bb) as i64;
+    let rare = 2 * hom_rare + n_ab as i64;
+    let mut het_probs = vec![0f64; rare as usize + 1];
+    let mut mid = rare * (2 * n - rare) / (2 * n);
+    if (rare & 1) != (mid & 1) {
+        mid += 1;
+    }
+    het_probs[mid as usize] = 1f64;
+    let mut sum = 1f64;
+    let (mut hets, mut homr, mut homc) = (mid, (rare - mid) / 2, n - mid - (rare - mid) / 2);
+    while hets > 1 {
+        het_probs[hets as usize - 2] = het_probs[hets as usize] * (hets * (hets - 1)) as f64
+            / (4 * (homr + 1) * (homc + 1)) as f64;
+        sum += het_probs[hets as usize - 2];
+        hets -= 2;
+        homr += 1;
+        homc += 1;
+    }
+    let (mut hets, mut homr, mut homc) = (mid, (rare - mid) / 2, n - mid - (rare - mid) / 2);
+    while hets <= rare - 2 {
+        het_probs[hets as usize + 2] =
+            het_probs[hets as usize] * (4 * homr * homc) as f64 / ((hets + 2) * (hets + 1)) as f64;
+        sum += het_probs[hets as usize + 2];
+        hets += 2;
+        homr -= 1;
+        homc -= 1;
+    }
+    let observed = het_probs[n_ab as usize] / sum;
+    het_probs
+        .iter()
+        .map(|p| p / sum)
+        .filter(|&p| p <= observed * (1f64 + 1e-8))
+        .sum::<f64>()
+        .min(1f64)
+}
@@ -2,26 +2,28 @@ use color_eyre::Report;
 use color_eyre::Result;
 use flate2::bufread::{ZlibDecoder, ZlibEncoder};
 use flate2::Compression;
+use itertools::Itertools;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Write};
 use std::path::Path;
 
-pub fn write_u16_sized_string(writer: &mut BufWriter<File>, string: String) -> Result<()> {
+pub fn write_u16_sized_string(writer: &mut impl Write, string: &str) -> Result<()> {
     let num = string.len() as u16;
     writer.write_all(&num.to_le_bytes())?;
-    writer.write_all(&string.into_bytes())?;
+    writer.write_all(string.as_bytes())?;
     Ok(())
 }
 
-pub fn write_u32_sized_string(writer: &mut BufWriter<File>, string: String) -> Result<()> {
+pub fn write_u32_sized_string(writer: &mut impl Write, string: &str) -> Result<()> {
     let num = string.len() as u32;
     writer.write_all(&num.to_le_bytes())?;
-    writer.write_all(&string.into_bytes())?;
+    writer.write_all(string.as_bytes())?;
     Ok(())
 }
 
-pub fn write_u8<T>(writer: &mut BufWriter<T>, num: u8) -> Result<()>
+pub fn write_u8<T>(writer: &mut T, num: u8) -> Result<()>
 where
     T: std::io::Write,
 {
@@ -29,7 +31,7 @@ where
     Ok(())
 }
 
-pub fn write_u16<T>(writer: &mut BufWriter<T>, num: u16) -> Result<()>
+pub fn write_u16<T>(writer: &mut T, num: u16) -> Result<()>
 where
     T: std::io::Write,
 {
@@ -37,7 +39,7 @@ where
     Ok(())
 }
 
-pub fn write_u32<T>(writer: &mut BufWriter<T>, num: u32) -> Result<()>
+pub fn write_u32<T>(writer: &mut T, num: u32) -> Result<()>
 where
     T: std::io::Write,
 {
@@ -45,6 +47,16 @@ where
     Ok(())
 }
 
+/// Opens `output_path` for writing, `-` stands for the standard output
+pub fn create_output(output_path: &str) -> Result<BufWriter<Box<dyn Write>>> {
+    let output: Box<dyn Write> = if output_path == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(output_path)?)
+    };
+    Ok(BufWriter::new(output))
+}
+
 pub fn read_lines<P>(filename: P) -> Result<Vec<String>>
 where
     P: AsRef<Path>,
@@ -56,6 +68,31 @@ where
     Ok(result)
 }
 
+/// Reads a file with two whitespace separated columns on each line, the old and the new
+/// name of something, into a map. Empty lines and lines starting with `#` are skipped,
+/// `description` names the file in error messages.
+pub fn read_two_column_mapping<P>(path: P, description: &str) -> Result<HashMap<String, String>>
+where
+    P: AsRef<Path>,
+{
+    read_lines(path)?
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(
+            |(i, line)| match line.split_whitespace().collect_vec()[..] {
+                [from, to] => Ok((from.to_string(), to.to_string())),
+                _ => Err(Report::msg(format!(
+                    "Line {} of {} file `{}` does not have two columns",
+                    i + 1,
+                    description,
+                    line
+                ))),
+            },
+        )
+        .collect()
+}
+
 pub fn compress_data(data: &Vec<u8>) -> Result<Vec<u8>> {
     let mut encoder = ZlibEncoder::new(Cursor::new(data), Compression::fast());
     let mut block = Vec::new();
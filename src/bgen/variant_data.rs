@@ -1,17 +1,24 @@
+use crate::bgen::bgen_stream::Ranges;
+use crate::bgen::header::HeaderFlags;
+use crate::bgen::range_set::RangeSet;
+use crate::bgen::stats::VariantStats;
 use crate::bgen::utils::{
     compress_data, write_u16, write_u16_sized_string, write_u32, write_u32_sized_string, write_u8,
 };
-use crate::parser::{Range, VariantOutput};
-use color_eyre::Result;
+use crate::list_writer::VariantRecord;
+use crate::parser::{variant_key, VariantOutput};
+use bitvec::prelude::*;
+use color_eyre::{Report, Result};
 use core::panic;
 use derivative::Derivative;
 use itertools::Itertools;
 use numtoa::NumToA;
-use std::fs::File;
+use std::collections::HashSet;
 use std::io::{BufWriter, Write};
 
 #[derive(Derivative)]
 #[derivative(Default, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariantData {
     pub number_individuals: Option<u32>,
     pub variants_id: String,
@@ -28,6 +35,7 @@ pub struct VariantData {
 }
 
 #[derive(Default, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DataBlock {
     pub number_individuals: u32,
     pub number_alleles: u16,
@@ -46,7 +54,11 @@ pub fn write_header(mut writer: impl Write, variant_output: &VariantOutput) -> R
             let line_header = b"alternate_ids\trsid\tchromosome\tposition\tnumber_of_alleles\tfirst_allele\talternative_alleles\n";
             writer.write_all(line_header)?;
         }
-        VariantOutput::Rsid => (),
+        VariantOutput::Tsv(fields_args) => {
+            let names = fields_args.fields.iter().map(|field| field.name());
+            writeln!(writer, "{}", names.collect_vec().join("\t"))?;
+        }
+        VariantOutput::Rsid | VariantOutput::Json(_) => (),
     }
     Ok(())
 }
@@ -56,6 +68,7 @@ impl VariantData {
         let mut buffer = [0u8; 20];
         Self::write_with_sep(&mut writer, self.variants_id.as_bytes())?;
         Self::write_with_sep(&mut writer, self.rsid.as_bytes())?;
+        Self::write_with_sep(&mut writer, self.chr.as_bytes())?;
         let b_pos = self.pos.numtoa(10, &mut buffer);
         Self::write_with_sep(&mut writer, b_pos)?;
         let b_number_alleles = self.number_alleles.numtoa(10, &mut buffer);
@@ -70,6 +83,10 @@ impl VariantData {
         match variant_output {
             VariantOutput::Bgenix => self.bgenix_print(writer),
             VariantOutput::Rsid => self.rsid_print(writer),
+            VariantOutput::Json(fields_args) => VariantRecord::new(self, &fields_args.fields)
+                .write_json_line(writer, &fields_args.fields),
+            VariantOutput::Tsv(fields_args) => VariantRecord::new(self, &fields_args.fields)
+                .write_tsv_line(writer, &fields_args.fields),
         }
     }
 
@@ -108,6 +125,7 @@ impl VariantData {
         }
         writer.write_all("GT:GP".as_bytes())?;
         writer.write_all(separator)?;
+        let max_probability = self.data_block.max_probability();
         let mut taken: usize = 0;
         for ploidy_miss in &self.data_block.ploidy_missingness {
             let missingness = ploidy_miss & (1 << 7);
@@ -120,7 +138,7 @@ impl VariantData {
             let (vec_calls, vec_geno) = if self.data_block.phased {
                 let vec_geno_phased_f = self.data_block.probabilities[taken..until]
                     .iter()
-                    .map(|&n| n as f64 / 65535f64)
+                    .map(|&n| n as f64 / max_probability)
                     .collect_vec();
                 let vec_calls = Self::geno_to_calls(&vec_geno_phased_f);
                 let vec_geno = vec_geno_phased_f
@@ -131,6 +149,7 @@ impl VariantData {
             } else {
                 let vec_calls_unphased = Self::calls_probabilities_unphased(
                     &self.data_block.probabilities[taken..until],
+                    max_probability,
                 );
                 let vec_geno_unphased = Self::calls_to_geno_unphased_raw(&vec_calls_unphased);
                 (vec_calls_unphased, vec_geno_unphased)
@@ -176,9 +195,9 @@ impl VariantData {
         vec_ret
     }
 
-    fn calls_probabilities_unphased(vec_geno: &[u32]) -> Vec<f64> {
+    fn calls_probabilities_unphased(vec_geno: &[u32], max_probability: f64) -> Vec<f64> {
         let mut vec_probas = Vec::with_capacity(3);
-        let mut iter_probas = vec_geno.iter().map(|e| *e as f64 / 65535f64);
+        let mut iter_probas = vec_geno.iter().map(|e| *e as f64 / max_probability);
         let p00 = iter_probas.next().unwrap();
         let p10 = iter_probas.next().unwrap();
         let p11 = 1f64 - p10 - p00;
@@ -188,86 +207,460 @@ impl VariantData {
         vec_probas
     }
 
-    pub fn filter_with_args(
+    pub fn filter_with_args(&self, ranges: &Ranges) -> bool {
+        if !ranges.allele_filters.accept(&self.alleles) {
+            return false;
+        }
+        let key = if ranges.incl_variant_keys.is_empty() && ranges.excl_variant_keys.is_empty() {
+            None
+        } else {
+            Some(self.variant_key(ranges.unordered_alleles))
+        };
+        let key = key.as_deref();
+        let in_excl = self.in_filters(
+            &ranges.excl_range,
+            &ranges.excl_rsids,
+            &ranges.excl_variant_ids,
+            &ranges.excl_variant_keys,
+            key,
+        );
+        // edge case: no inclusion filters, all variants are included if not excluded
+        let selected = if !ranges.has_inclusion() {
+            !in_excl
+        } else {
+            self.in_filters(
+                &ranges.incl_range,
+                &ranges.incl_rsids,
+                &ranges.incl_variant_ids,
+                &ranges.incl_variant_keys,
+                key,
+            ) && !in_excl
+        };
+        // statistics are only computed for variants that pass the cheaper filters
+        selected
+            && (ranges.stat_filters.is_empty()
+                || ranges
+                    .stat_filters
+                    .accept(&VariantStats::from_variant(self)))
+    }
+
+    fn in_filters(
         &self,
-        incl_ranges: &[Range],
-        incl_rsids: &[std::string::String],
-        excl_ranges: &[Range],
-        excl_rsid: &[std::string::String],
+        ranges: &RangeSet,
+        rsids: &HashSet<String>,
+        variant_ids: &HashSet<String>,
+        keys: &HashSet<String>,
+        key: Option<&str>,
     ) -> bool {
-        // edge case: no inclusion filters, all variants are included if not excluded
-        if incl_ranges.is_empty() && incl_rsids.is_empty() {
-            return !self.in_filters(excl_ranges, excl_rsid);
-        }
-        self.in_filters(incl_ranges, incl_rsids) && !self.in_filters(excl_ranges, excl_rsid)
+        ranges.contains(&self.chr, self.pos)
+            || rsids.contains(&self.rsid)
+            || variant_ids.contains(&self.variants_id)
+            || key.is_some_and(|key| keys.contains(key))
     }
 
-    fn in_filters(&self, ranges: &[Range], rsids: &[std::string::String]) -> bool {
-        let in_ranges = ranges.iter().any(|r| self.in_range(r));
-        let in_rsids = rsids.iter().any(|r| &self.rsid == r);
-        in_rsids || in_ranges
+    /// Normalised chr:pos:ref:alt key of the variant, see `normalise_variant_key`
+    pub fn variant_key(&self, unordered_alleles: bool) -> String {
+        let alleles = self
+            .alleles
+            .iter()
+            .map(|a| a.to_ascii_uppercase())
+            .collect();
+        variant_key(&self.chr, self.pos, alleles, unordered_alleles)
     }
 
-    fn in_range(&self, range: &Range) -> bool {
-        range.chr == self.chr && range.start <= self.pos && self.pos <= range.end
+    /// Writes the variant in the layout and with the compression of `header_flags`
+    pub fn write_self(self, writer: &mut impl Write, header_flags: &HeaderFlags) -> Result<()> {
+        self.write_identifying_block(writer, header_flags.layout_id)?;
+        Self::write_data_block(writer, &self.data_block, header_flags)?;
+        Ok(())
     }
 
-    pub fn write_self(self, writer: &mut BufWriter<File>, layout_id: u8) -> Result<()> {
+    pub fn write_identifying_block(&self, writer: &mut impl Write, layout_id: u8) -> Result<()> {
         if layout_id == 1 {
-            write_u32(writer, self.number_individuals.unwrap())?;
+            let number_individuals = self
+                .number_individuals
+                .unwrap_or(self.data_block.number_individuals);
+            write_u32(writer, number_individuals)?;
         }
-        write_u16_sized_string(writer, self.variants_id)?;
-        write_u16_sized_string(writer, self.rsid)?;
-        write_u16_sized_string(writer, self.chr)?;
+        write_u16_sized_string(writer, &self.variants_id)?;
+        write_u16_sized_string(writer, &self.rsid)?;
+        write_u16_sized_string(writer, &self.chr)?;
         write_u32(writer, self.pos)?;
         if layout_id != 1 {
             write_u16(writer, self.number_alleles)?;
         }
         self.alleles
-            .into_iter()
-            .map(|allele| write_u32_sized_string(writer, allele))
-            .collect::<Result<Vec<_>>>()?;
-        Self::write_data_block(writer, self.data_block)?;
+            .iter()
+            .try_for_each(|allele| write_u32_sized_string(writer, allele))?;
         Ok(())
     }
 
-    fn write_data_block(writer: &mut BufWriter<File>, data_block: DataBlock) -> Result<()> {
-        let mut data = Vec::new();
-        let mut data_writer = BufWriter::new(&mut data);
-        write_u32(&mut data_writer, data_block.number_individuals)?;
-        write_u16(&mut data_writer, data_block.number_alleles)?;
-        write_u8(&mut data_writer, data_block.minimum_ploidy)?;
-        write_u8(&mut data_writer, data_block.maximum_ploidy)?;
-        data_block
-            .ploidy_missingness
+    fn write_data_block(
+        writer: &mut impl Write,
+        data_block: &DataBlock,
+        header_flags: &HeaderFlags,
+    ) -> Result<()> {
+        let layout_1 = header_flags.layout_id == 1;
+        let data = if layout_1 {
+            data_block.layout_1_bytes()?
+        } else {
+            data_block.layout_2_bytes()?
+        };
+        if !header_flags.compressed_snp_blocks {
+            // uncompressed layout 1 blocks have a fixed size and no length
+            if !layout_1 {
+                write_u32(writer, data.len() as u32)?;
+            }
+            writer.write_all(&data)?;
+            return Ok(());
+        }
+        let block = compress_data(&data)?;
+        if layout_1 {
+            write_u32(writer, block.len() as u32)?;
+        } else {
+            write_u32(writer, block.len() as u32 + 4)?;
+            write_u32(writer, data.len() as u32)?;
+        }
+        writer.write_all(&block)?;
+        Ok(())
+    }
+}
+
+/// Probabilities of one sample, with the value left implicit by the bgen encoding restored
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleProbabilities {
+    pub ploidy: u8,
+    pub missing: bool,
+    pub probabilities: Vec<f64>,
+}
+
+impl DataBlock {
+    /// Largest value a probability can be stored as, which stands for a probability of 1
+    pub fn max_probability(&self) -> f64 {
+        ((1u64 << self.bytes_probability) - 1) as f64
+    }
+
+    /// Number of probabilities stored for a sample of the given ploidy
+    pub fn stored_values(&self, ploidy: u8) -> usize {
+        let alleles = self.number_alleles as usize;
+        if self.phased {
+            ploidy as usize * (alleles - 1)
+        } else {
+            binomial(ploidy as usize + alleles - 1, alleles - 1) - 1
+        }
+    }
+
+    /// Number of probabilities of a sample of the given ploidy, including the one left
+    /// implicit in the file for each genotype or haplotype
+    pub fn probabilities_per_sample(&self, ploidy: u8) -> usize {
+        let groups = if self.phased { ploidy as usize } else { 1 };
+        self.stored_values(ploidy) + groups
+    }
+
+    /// Decodes the stored probabilities of every sample. For phased data, probabilities
+    /// are given haplotype after haplotype, one per allele. For unphased data, they are
+    /// given for every genotype, in the order of the bgen specification.
+    pub fn sample_probabilities(&self) -> Vec<SampleProbabilities> {
+        let max_probability = self.max_probability();
+        let mut taken = 0;
+        self.ploidy_missingness
+            .iter()
+            .map(|ploidy_miss| {
+                let ploidy = ploidy_miss & 0x7f;
+                let missing = ploidy_miss & 0x80 != 0;
+                let until = taken + self.stored_values(ploidy);
+                let stored = self.probabilities[taken..until]
+                    .iter()
+                    .map(|&p| p as f64 / max_probability);
+                taken = until;
+                let probabilities = if self.phased {
+                    let per_haplotype = self.number_alleles as usize - 1;
+                    stored
+                        .collect_vec()
+                        .chunks(per_haplotype.max(1))
+                        .take(ploidy as usize)
+                        .flat_map(|chunk| with_implied_last(chunk.iter().copied()))
+                        .collect()
+                } else {
+                    with_implied_last(stored)
+                };
+                SampleProbabilities {
+                    ploidy,
+                    missing,
+                    probabilities,
+                }
+            })
+            .collect()
+    }
+
+    /// Expected number of copies of the second allele for each sample of a biallelic
+    /// variant, `None` for missing samples. Fails for other variants.
+    pub fn dosages(&self) -> Result<Vec<Option<f64>>> {
+        if self.number_alleles != 2 {
+            return Err(Report::msg(format!(
+                "Dosages require a biallelic variant, not one with {} alleles",
+                self.number_alleles
+            )));
+        }
+        Ok(self
+            .sample_probabilities()
             .into_iter()
-            .try_for_each(|p| write_u8(&mut data_writer, p))?;
-        write_u8(&mut data_writer, data_block.phased as u8)?;
-        write_u8(&mut data_writer, data_block.bytes_probability)?;
-        assert_eq!(data_block.bytes_probability % 8, 0);
-        let chunk_size = (data_block.bytes_probability / 8) as usize;
-        data_block
-            .probabilities
+            .map(|sample| {
+                if sample.missing {
+                    return None;
+                }
+                let dosage = if self.phased {
+                    sample.probabilities.iter().skip(1).step_by(2).sum()
+                } else {
+                    sample
+                        .probabilities
+                        .iter()
+                        .enumerate()
+                        .map(|(copies, p)| copies as f64 * p)
+                        .sum()
+                };
+                Some(dosage)
+            })
+            .collect())
+    }
+}
+
+impl DataBlock {
+    /// Largest difference between the dosages of two data blocks of the same biallelic
+    /// variant, `None` for other variants. Samples missing in either block are ignored.
+    pub fn max_dosage_error(&self, other: &DataBlock) -> Option<f64> {
+        if self.number_alleles != 2 || other.number_alleles != 2 {
+            return None;
+        }
+        let max_error = self
+            .dosages()
+            .ok()?
             .into_iter()
-            .try_for_each(|probability| {
-                probability
-                    .to_le_bytes()
-                    .into_iter()
-                    .take(chunk_size)
-                    .try_for_each(|byte_proba| write_u8(&mut data_writer, byte_proba))
-            })?;
+            .zip(other.dosages().ok()?)
+            .filter_map(|(dosage, other_dosage)| Some((dosage? - other_dosage?).abs()))
+            .fold(0f64, f64::max);
+        Some(max_error)
+    }
+
+    /// Stores the probabilities of each sample on `bits` bits, rounded as described in the
+    /// bgen specification. Samples give every probability, including the one left implicit
+    /// in the file: one per allele and haplotype for phased data, one per genotype otherwise.
+    pub fn from_sample_probabilities(
+        samples: &[SampleProbabilities],
+        number_alleles: u16,
+        phased: bool,
+        bits: u8,
+    ) -> Result<DataBlock> {
+        if !(1..=32).contains(&bits) {
+            return Err(Report::msg(format!(
+                "Probabilities are stored on 1 to 32 bits, not {}",
+                bits
+            )));
+        }
+        let mut data_block = DataBlock {
+            number_individuals: samples.len() as u32,
+            number_alleles,
+            minimum_ploidy: samples.iter().map(|s| s.ploidy).min().unwrap_or(0),
+            maximum_ploidy: samples.iter().map(|s| s.ploidy).max().unwrap_or(0),
+            ploidy_missingness: Vec::with_capacity(samples.len()),
+            phased,
+            bytes_probability: bits,
+            probabilities: Vec::new(),
+        };
+        let max_value = (1u64 << bits) - 1;
+        let per_group = if phased { number_alleles as usize } else { 0 };
+        for (i, sample) in samples.iter().enumerate() {
+            let stored_values = data_block.stored_values(sample.ploidy);
+            let expected = if phased {
+                sample.ploidy as usize * per_group
+            } else {
+                stored_values + 1
+            };
+            if sample.probabilities.len() != expected {
+                return Err(Report::msg(format!(
+                    "Sample {} has {} probabilities instead of {}",
+                    i + 1,
+                    sample.probabilities.len(),
+                    expected
+                )));
+            }
+            data_block
+                .ploidy_missingness
+                .push(sample.ploidy | ((sample.missing as u8) << 7));
+            if sample.missing {
+                data_block
+                    .probabilities
+                    .extend(std::iter::repeat_n(0, stored_values));
+                continue;
+            }
+            // the last probability of each group is implied by the others
+            let groups = if phased {
+                sample.probabilities.chunks(per_group).collect_vec()
+            } else {
+                vec![&sample.probabilities[..]]
+            };
+            for group in groups {
+                let rounded = round_probabilities(group, max_value);
+                data_block.probabilities.extend(
+                    rounded[..rounded.len() - 1]
+                        .iter()
+                        .map(|&value| value as u32),
+                );
+            }
+        }
+        Ok(data_block)
+    }
+
+    /// Same probabilities, stored on `bits` bits with the rounding of the bgen
+    /// specification, which keeps the probabilities of each sample summing to one
+    pub fn with_bits(&self, bits: u8) -> Result<DataBlock> {
+        Self::from_sample_probabilities(
+            &self.sample_probabilities(),
+            self.number_alleles,
+            self.phased,
+            bits,
+        )
+    }
+
+    /// Decodes an uncompressed layout 1 data block, where each sample has three
+    /// probabilities on 16 bits scaled by 32768. They are stored here on 16 bits.
+    pub fn from_layout_1_bytes(bytes: &[u8]) -> Result<DataBlock> {
+        if !bytes.len().is_multiple_of(6) {
+            return Err(Report::msg(
+                "Layout 1 data block is not made of 6 bytes per sample",
+            ));
+        }
+        let samples = bytes
+            .chunks_exact(6)
+            .map(|sample| {
+                let probabilities = sample
+                    .chunks_exact(2)
+                    .map(|p| u16::from_le_bytes([p[0], p[1]]) as f64 / LAYOUT_1_SCALE)
+                    .collect_vec();
+                // layout 1 has no missingness flag, samples without probabilities are missing
+                let missing = probabilities.iter().all(|&p| p == 0f64);
+                SampleProbabilities {
+                    ploidy: 2,
+                    missing,
+                    probabilities,
+                }
+            })
+            .collect_vec();
+        Self::from_sample_probabilities(&samples, 2, false, 16)
+    }
+
+    /// Uncompressed layout 1 data block, only possible for unphased, biallelic and diploid
+    /// data
+    pub fn layout_1_bytes(&self) -> Result<Vec<u8>> {
+        if self.number_alleles != 2 || self.phased {
+            return Err(Report::msg(
+                "Layout 1 only stores unphased variants with two alleles",
+            ));
+        }
+        let mut bytes = Vec::with_capacity(6 * self.number_individuals as usize);
+        for sample in self.sample_probabilities() {
+            if sample.ploidy != 2 {
+                return Err(Report::msg("Layout 1 only stores diploid samples"));
+            }
+            for probability in sample.probabilities {
+                let value = if sample.missing {
+                    0
+                } else {
+                    (probability * LAYOUT_1_SCALE).round() as u16
+                };
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Uncompressed layout 2 data block
+    pub fn layout_2_bytes(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut data_writer = BufWriter::new(&mut data);
+        write_u32(&mut data_writer, self.number_individuals)?;
+        write_u16(&mut data_writer, self.number_alleles)?;
+        write_u8(&mut data_writer, self.minimum_ploidy)?;
+        write_u8(&mut data_writer, self.maximum_ploidy)?;
+        self.ploidy_missingness
+            .iter()
+            .try_for_each(|&p| write_u8(&mut data_writer, p))?;
+        write_u8(&mut data_writer, self.phased as u8)?;
+        write_u8(&mut data_writer, self.bytes_probability)?;
+        data_writer.write_all(&pack_probabilities(
+            &self.probabilities,
+            self.bytes_probability,
+        ))?;
         data_writer.flush()?;
         drop(data_writer);
-        let uncompressed_length = data.len() as u32;
-        let block = compress_data(&data)?;
-        let length_data_block = block.len() as u32 + 4;
-        write_u32(writer, length_data_block)?;
-        write_u32(writer, uncompressed_length)?;
-        writer.write_all(&block)?;
-        Ok(())
+        Ok(data)
     }
 }
 
+/// Value standing for a probability of 1 in layout 1
+const LAYOUT_1_SCALE: f64 = 32768f64;
+
+/// Rounds probabilities to integers summing to `max_value`, following the bgen
+/// specification: scaled probabilities are rounded down, then those with the largest
+/// fractional parts are rounded up until the sum is reached
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::bgen::variant_data::round_probabilities;
+/// assert_eq!(round_probabilities(&[0.5, 0.3, 0.2], 255), vec![128, 76, 51]);
+/// assert_eq!(round_probabilities(&[1.0 / 3.0; 3], 255), vec![85, 85, 85]);
+/// ```
+pub fn round_probabilities(probabilities: &[f64], max_value: u64) -> Vec<u64> {
+    let total: f64 = probabilities.iter().map(|p| p.max(0f64)).sum();
+    if total <= 0f64 {
+        return vec![0; probabilities.len()];
+    }
+    let scaled = probabilities
+        .iter()
+        .map(|p| p.max(0f64) / total * max_value as f64)
+        .collect_vec();
+    let mut rounded = scaled.iter().map(|s| s.floor() as u64).collect_vec();
+    let remaining = max_value.saturating_sub(rounded.iter().sum());
+    let by_fraction = (0..scaled.len())
+        .sorted_by(|&a, &b| {
+            let fraction = |i: usize| scaled[i] - scaled[i].floor();
+            fraction(b).total_cmp(&fraction(a))
+        })
+        .collect_vec();
+    for &i in by_fraction.iter().take(remaining as usize) {
+        rounded[i] += 1;
+    }
+    rounded
+}
+
+/// Packs probabilities on `bits` bits each, least significant bit first
+fn pack_probabilities(probabilities: &[u32], bits: u8) -> Vec<u8> {
+    if bits.is_multiple_of(8) {
+        let chunk_size = (bits / 8) as usize;
+        return probabilities
+            .iter()
+            .flat_map(|p| p.to_le_bytes().into_iter().take(chunk_size))
+            .collect();
+    }
+    let mut packed: BitVec<u8, Lsb0> = BitVec::with_capacity(probabilities.len() * bits as usize);
+    for probability in probabilities {
+        (0..bits).for_each(|i| packed.push((probability >> i) & 1 == 1));
+    }
+    packed.into_vec()
+}
+
+fn with_implied_last(stored: impl Iterator<Item = f64>) -> Vec<f64> {
+    let mut probabilities = stored.collect_vec();
+    let implied = 1f64 - probabilities.iter().sum::<f64>();
+    probabilities.push(implied.max(0f64));
+    probabilities
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
 /// # Examples
 /// ```
 /// # use bgen_reader::bgen::variant_data::f64_round;
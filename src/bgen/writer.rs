@@ -0,0 +1,306 @@
+use crate::bgen::bgen_stream::write_header_and_samples;
+use crate::bgen::header::{Header, HeaderFlags, VARIANT_NUM_OFFSET};
+use crate::bgen::utils::write_u32;
+use crate::bgen::variant_data::{DataBlock, SampleProbabilities, VariantData};
+use color_eyre::{Report, Result};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+/// Settings of a new bgen file, see [`BgenWriter::builder`]
+#[derive(Clone, Debug)]
+pub struct BgenWriterBuilder {
+    sample_num: u32,
+    layout_id: u8,
+    compressed: bool,
+    bits: u8,
+    ploidy: u8,
+    phased: bool,
+    samples: Vec<String>,
+    free_data: Vec<u8>,
+}
+
+impl BgenWriterBuilder {
+    /// Layout 2 by default, layout 1 only holds unphased, biallelic and diploid data
+    pub fn with_layout(mut self, layout_id: u8) -> Self {
+        self.layout_id = layout_id;
+        self
+    }
+
+    /// Compresses the data blocks with zlib, the default
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Number of bits of the probabilities in layout 2, 16 by default
+    pub fn with_bits(mut self, bits: u8) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    /// Ploidy of every sample, 2 by default
+    pub fn with_ploidy(mut self, ploidy: u8) -> Self {
+        self.ploidy = ploidy;
+        self
+    }
+
+    /// Probabilities are given per haplotype instead of per genotype
+    pub fn with_phased(mut self, phased: bool) -> Self {
+        self.phased = phased;
+        self
+    }
+
+    /// Sample identifiers embedded in the file, none by default
+    pub fn with_samples(mut self, samples: Vec<String>) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Content of the free data area of the header
+    pub fn with_free_data(mut self, free_data: Vec<u8>) -> Self {
+        self.free_data = free_data;
+        self
+    }
+
+    /// Creates the file at `output_path` and writes its header and samples
+    pub fn create(self, output_path: &str) -> Result<BgenWriter<File>> {
+        self.validate()?;
+        self.build(File::create(output_path)?)
+    }
+
+    /// Writes the header and samples to `writer`, which must be seekable for the number of
+    /// variants to be patched in the end
+    pub fn build<W: Write + Seek>(self, writer: W) -> Result<BgenWriter<W>> {
+        self.validate()?;
+        let mut header = Header {
+            sample_num: self.sample_num,
+            header_flags: HeaderFlags {
+                compressed_snp_blocks: self.compressed,
+                layout_id: self.layout_id,
+                sample_id_present: !self.samples.is_empty(),
+            },
+            ..Default::default()
+        };
+        header.set_free_data(self.free_data);
+        let mut writer = BufWriter::new(writer);
+        let start_position = writer.stream_position()?;
+        write_header_and_samples(
+            &mut writer,
+            &header,
+            &self.samples,
+            0,
+            self.samples.is_empty(),
+        )?;
+        Ok(BgenWriter {
+            writer,
+            header_flags: header.header_flags,
+            sample_num: self.sample_num,
+            bits: self.bits,
+            ploidy: self.ploidy,
+            phased: self.phased,
+            variant_num: 0,
+            start_position,
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(1..=2).contains(&self.layout_id) {
+            return Err(Report::msg(format!("Unknown layout {}", self.layout_id)));
+        }
+        if self.layout_id == 1 && self.bits != 16 {
+            return Err(Report::msg(
+                "Layout 1 always stores probabilities on 16 bits",
+            ));
+        }
+        if self.layout_id == 1 && (self.phased || self.ploidy != 2) {
+            return Err(Report::msg(
+                "Layout 1 only holds unphased diploid probabilities",
+            ));
+        }
+        if !(1..=32).contains(&self.bits) {
+            return Err(Report::msg(format!(
+                "Probabilities are stored on 1 to 32 bits, not {}",
+                self.bits
+            )));
+        }
+        if !(1..=63).contains(&self.ploidy) {
+            return Err(Report::msg(format!(
+                "Ploidy must be between 1 and 63, not {}",
+                self.ploidy
+            )));
+        }
+        if !self.samples.is_empty() && self.samples.len() != self.sample_num as usize {
+            return Err(Report::msg(format!(
+                "{} sample identifiers given for {} samples",
+                self.samples.len(),
+                self.sample_num
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Writes a bgen file variant by variant. The number of variants of the header is
+/// patched by [`BgenWriter::finish`].
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::bgen::bgen_stream::BgenStream;
+/// # use bgen_reader::bgen::writer::BgenWriter;
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let path = dir.path().join("simulated.bgen");
+/// # let path = path.to_str().unwrap();
+/// let mut writer = BgenWriter::builder(2)
+///     .with_samples(vec!["sample_1".to_string(), "sample_2".to_string()])
+///     .with_bits(8)
+///     .create(path)
+///     .unwrap();
+/// let alleles = ["A".to_string(), "G".to_string()];
+/// writer
+///     .write_variant("var_1", "rs1", "1", 1000, &alleles, &[0.9, 0.1, 0.0, 0.0, 0.5, 0.5])
+///     .unwrap();
+/// assert_eq!(1, writer.finish().unwrap());
+///
+/// let mut bgen_stream = BgenStream::from_path(path, false, true).unwrap();
+/// bgen_stream.read_offset_and_header().unwrap();
+/// assert_eq!(1, bgen_stream.header.variant_num);
+/// let variant = bgen_stream.next().unwrap().unwrap();
+/// assert_eq!("rs1", variant.rsid);
+/// ```
+pub struct BgenWriter<W: Write + Seek> {
+    writer: BufWriter<W>,
+    header_flags: HeaderFlags,
+    sample_num: u32,
+    bits: u8,
+    ploidy: u8,
+    phased: bool,
+    variant_num: u32,
+    /// Position of the header in the writer, which may not be at its start
+    start_position: u64,
+}
+
+impl BgenWriter<File> {
+    /// Settings of a file with `sample_num` samples, in layout 2 with zlib compression and
+    /// unphased diploid probabilities on 16 bits unless changed
+    pub fn builder(sample_num: u32) -> BgenWriterBuilder {
+        BgenWriterBuilder {
+            sample_num,
+            layout_id: 2,
+            compressed: true,
+            bits: 16,
+            ploidy: 2,
+            phased: false,
+            samples: Vec::new(),
+            free_data: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write + Seek> BgenWriter<W> {
+    /// Writes a variant. `probabilities` holds every probability of each sample, sample
+    /// after sample: one per genotype, in the order of the bgen specification, for unphased
+    /// data, one per allele and haplotype for phased data. A sample with a NaN probability
+    /// is written as missing.
+    pub fn write_variant(
+        &mut self,
+        variant_id: &str,
+        rsid: &str,
+        chr: &str,
+        pos: u32,
+        alleles: &[String],
+        probabilities: &[f64],
+    ) -> Result<()> {
+        let number_alleles = u16::try_from(alleles.len())
+            .map_err(|_| Report::msg(format!("Too many alleles for variant {}", rsid)))?;
+        if number_alleles < 2 {
+            return Err(Report::msg(format!(
+                "Variant {} needs at least two alleles",
+                rsid
+            )));
+        }
+        if self.header_flags.layout_id == 1 && number_alleles != 2 {
+            return Err(Report::msg(format!(
+                "Layout 1 only holds biallelic variants, {} has {} alleles",
+                rsid, number_alleles
+            )));
+        }
+        let per_sample = self.probabilities_per_sample(number_alleles);
+        if probabilities.len() != per_sample * self.sample_num as usize {
+            return Err(Report::msg(format!(
+                "Variant {} has {} probabilities instead of {} for {} samples",
+                rsid,
+                probabilities.len(),
+                per_sample * self.sample_num as usize,
+                self.sample_num
+            )));
+        }
+        let samples: Vec<_> = probabilities
+            .chunks(per_sample.max(1))
+            .take(self.sample_num as usize)
+            .map(|sample| {
+                let missing = sample.iter().any(|p| p.is_nan());
+                SampleProbabilities {
+                    ploidy: self.ploidy,
+                    missing,
+                    probabilities: if missing {
+                        vec![0f64; sample.len()]
+                    } else {
+                        sample.to_vec()
+                    },
+                }
+            })
+            .collect();
+        let data_block =
+            DataBlock::from_sample_probabilities(&samples, number_alleles, self.phased, self.bits)?;
+        let variant_data = VariantData {
+            number_individuals: (self.header_flags.layout_id == 1).then_some(self.sample_num),
+            variants_id: variant_id.to_string(),
+            rsid: rsid.to_string(),
+            chr: chr.to_string(),
+            pos,
+            number_alleles,
+            alleles: alleles.to_vec(),
+            data_block,
+            ..Default::default()
+        };
+        variant_data.write_self(&mut self.writer, &self.header_flags)?;
+        self.variant_num = self
+            .variant_num
+            .checked_add(1)
+            .ok_or_else(|| Report::msg("Too many variants for a bgen file"))?;
+        Ok(())
+    }
+
+    /// Patches the number of variants of the header and flushes the file. Returns the
+    /// number of variants written.
+    pub fn finish(mut self) -> Result<u32> {
+        self.patch_variant_num()?;
+        Ok(self.variant_num)
+    }
+
+    /// Finishes the file like [`BgenWriter::finish`] and returns the underlying writer,
+    /// positioned after the last variant
+    pub fn into_inner(mut self) -> Result<W> {
+        self.patch_variant_num()?;
+        self.writer.into_inner().map_err(|e| e.into_error().into())
+    }
+
+    fn patch_variant_num(&mut self) -> Result<()> {
+        let end_position = self.writer.stream_position()?;
+        self.writer
+            .seek(SeekFrom::Start(self.start_position + VARIANT_NUM_OFFSET))?;
+        write_u32(&mut self.writer, self.variant_num)?;
+        self.writer.seek(SeekFrom::Start(end_position))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn probabilities_per_sample(&self, number_alleles: u16) -> usize {
+        let data_block = DataBlock {
+            number_alleles,
+            phased: self.phased,
+            ..Default::default()
+        };
+        data_block.probabilities_per_sample(self.ploidy)
+    }
+}
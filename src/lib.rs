@@ -1,3 +1,4 @@
 pub mod bgen;
+pub mod list_writer;
 pub mod parser;
 pub mod vcf_writer;
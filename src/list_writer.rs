@@ -0,0 +1,207 @@
+use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::stats::VariantStats;
+use crate::bgen::variant_data::{write_header, VariantData};
+use crate::parser::VariantOutput;
+use clap::ValueEnum;
+use color_eyre::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::io::{Read, Write};
+
+/// Field of a variant in the structured outputs of `list`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[value(rename_all = "snake_case")]
+pub enum ListField {
+    Chr,
+    Pos,
+    Rsid,
+    AlternateId,
+    Alleles,
+    /// Offset of the variant in the file
+    FileOffset,
+    SizeInBytes,
+    AlleleBFrequency,
+    MinorAlleleFrequency,
+    ImputeInfo,
+    MachR2,
+    HwePValue,
+    MissingProportion,
+}
+
+impl ListField {
+    pub const DEFAULT_FIELDS: [ListField; 5] = [
+        ListField::Chr,
+        ListField::Pos,
+        ListField::Rsid,
+        ListField::AlternateId,
+        ListField::Alleles,
+    ];
+
+    /// Name of the field, in the header of the TSV output and as a JSON key
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .expect("list fields are never skipped")
+            .get_name()
+            .to_string()
+    }
+
+    /// Statistics are computed from the data block of the variant
+    pub fn is_stat(&self) -> bool {
+        !matches!(
+            self,
+            ListField::Chr
+                | ListField::Pos
+                | ListField::Rsid
+                | ListField::AlternateId
+                | ListField::Alleles
+                | ListField::FileOffset
+                | ListField::SizeInBytes
+        )
+    }
+}
+
+/// Variant as written by the structured outputs of `list`, only the selected fields are set.
+/// Statistics that are not defined for a variant are written as `null`.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct VariantRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alternate_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alleles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_in_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allele_b_frequency: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minor_allele_frequency: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impute_info: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mach_r2: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hwe_p_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_proportion: Option<f64>,
+}
+
+impl VariantRecord {
+    /// Record with the `fields` of the variant, whose data block must have been read if a
+    /// statistic is selected
+    pub fn new(variant_data: &VariantData, fields: &[ListField]) -> Self {
+        let stats = fields
+            .iter()
+            .any(|field| field.is_stat())
+            .then(|| VariantStats::from_variant(variant_data));
+        let mut record = VariantRecord::default();
+        for field in fields {
+            let stat = |value: fn(&VariantStats) -> f64| stats.as_ref().map(value);
+            match field {
+                ListField::Chr => record.chr = Some(variant_data.chr.clone()),
+                ListField::Pos => record.pos = Some(variant_data.pos),
+                ListField::Rsid => record.rsid = Some(variant_data.rsid.clone()),
+                ListField::AlternateId => {
+                    record.alternate_id = Some(variant_data.variants_id.clone())
+                }
+                ListField::Alleles => record.alleles = Some(variant_data.alleles.clone()),
+                ListField::FileOffset => {
+                    record.file_offset = Some(variant_data.file_start_position as u64)
+                }
+                ListField::SizeInBytes => {
+                    record.size_in_bytes = Some(variant_data.size_in_bytes as u64)
+                }
+                ListField::AlleleBFrequency => {
+                    record.allele_b_frequency = stat(|s| s.allele_b_frequency)
+                }
+                ListField::MinorAlleleFrequency => {
+                    record.minor_allele_frequency = stat(|s| s.minor_allele_frequency)
+                }
+                ListField::ImputeInfo => record.impute_info = stat(|s| s.impute_info),
+                ListField::MachR2 => record.mach_r2 = stat(|s| s.mach_r2),
+                ListField::HwePValue => record.hwe_p_value = stat(|s| s.hwe_p_value),
+                ListField::MissingProportion => {
+                    record.missing_proportion = stat(|s| s.missing_proportion)
+                }
+            }
+        }
+        record
+    }
+
+    /// JSON object of the `fields` of the record, in the order of `fields`
+    pub fn to_json(&self, fields: &[ListField]) -> Result<Value> {
+        let mut values = match serde_json::to_value(self)? {
+            Value::Object(values) => values,
+            _ => Map::new(),
+        };
+        Ok(Value::Object(
+            fields
+                .iter()
+                .filter_map(|field| {
+                    let name = field.name();
+                    values.remove(&name).map(|value| (name, value))
+                })
+                .collect(),
+        ))
+    }
+
+    /// Writes the `fields` of the record as one line of JSON
+    pub fn write_json_line(&self, mut writer: impl Write, fields: &[ListField]) -> Result<()> {
+        serde_json::to_writer(&mut writer, &self.to_json(fields)?)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Writes the `fields` of the record as one TSV line. Alleles are separated by commas,
+    /// empty and undefined values are written as `.`
+    pub fn write_tsv_line(&self, mut writer: impl Write, fields: &[ListField]) -> Result<()> {
+        let text = |value: &Option<String>| match value {
+            Some(value) if !value.is_empty() => value.clone(),
+            _ => ".".to_string(),
+        };
+        let number = |value: Option<u64>| value.map_or(".".to_string(), |v| v.to_string());
+        let float = |value: Option<f64>| match value {
+            Some(value) if value.is_finite() => ryu::Buffer::new().format(value).to_string(),
+            _ => ".".to_string(),
+        };
+        let line = fields
+            .iter()
+            .map(|field| match field {
+                ListField::Chr => text(&self.chr),
+                ListField::Pos => number(self.pos.map(u64::from)),
+                ListField::Rsid => text(&self.rsid),
+                ListField::AlternateId => text(&self.alternate_id),
+                ListField::Alleles => text(&self.alleles.as_ref().map(|a| a.join(","))),
+                ListField::FileOffset => number(self.file_offset),
+                ListField::SizeInBytes => number(self.size_in_bytes),
+                ListField::AlleleBFrequency => float(self.allele_b_frequency),
+                ListField::MinorAlleleFrequency => float(self.minor_allele_frequency),
+                ListField::ImputeInfo => float(self.impute_info),
+                ListField::MachR2 => float(self.mach_r2),
+                ListField::HwePValue => float(self.hwe_p_value),
+                ListField::MissingProportion => float(self.missing_proportion),
+            })
+            .collect::<Vec<_>>()
+            .join("\t");
+        writeln!(writer, "{}", line)?;
+        Ok(())
+    }
+}
+
+/// Writes the variants of the stream in the format of `variant_output`, after its header
+pub fn write_list<T: Read>(
+    mut writer: impl Write,
+    bgen_stream: BgenStream<T>,
+    variant_output: &VariantOutput,
+) -> Result<()> {
+    write_header(&mut writer, variant_output)?;
+    bgen_stream
+        .into_iter()
+        .try_for_each(|variant_data| variant_data?.print(&mut writer, variant_output))
+}
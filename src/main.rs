@@ -1,14 +1,14 @@
-use bgen_reader::bgen::bgen_stream::{bgen_merge, BgenStream, MetadataBgi};
-use bgen_reader::bgen::bgi_writer::TableCreator;
-use bgen_reader::bgen::variant_data::write_header;
+use bgen_reader::bgen::bgen_stream::{bgen_merge, BgenStream};
+use bgen_reader::bgen::chromosome::ChrOrder;
+use bgen_reader::bgen::info::BgenInfo;
+use bgen_reader::bgen::{append, bgi_writer, chunks, reheader, sort, split, stats};
 use bgen_reader::parser::{Cli, Command, VariantOutput};
-use bgen_reader::vcf_writer;
+use bgen_reader::{list_writer, vcf_writer};
 use clap::Parser;
-use color_eyre::Report;
 use color_eyre::Result;
 use env_logger::Builder;
 use log::LevelFilter;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 
 fn main() -> Result<()> {
     color_eyre::install()?;
@@ -20,53 +20,164 @@ fn main() -> Result<()> {
     }
     match cli.command {
         Command::Index => {
-            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, false)?;
+            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, false)?
+                .with_sample_policy(cli.sample_policy);
             bgen_stream.read_offset_and_header()?;
-            let bgi_filename = cli.filename.to_string() + ".bgi_rust";
-            let table_creator = TableCreator::new(bgi_filename)?;
-            let file_metadata = match bgen_stream.metadata.clone() {
-                MetadataBgi::File(file_metadata) => file_metadata,
-                _ => {
-                    return Err(Report::msg(
-                        "No file metadata in bgen constructed from file",
-                    ))
-                }
-            };
-            table_creator.init(&file_metadata)?;
-            table_creator.store(bgen_stream)?;
+            bgi_writer::write_index(bgen_stream)?;
         }
         Command::List(filter_args_list) => {
-            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, false)?;
+            let var_output: VariantOutput = filter_args_list.variant_output.unwrap_or_default();
+            let mut bgen_stream = BgenStream::from_input(
+                &cli.filename,
+                cli.use_sample_file,
+                var_output.needs_data_block(),
+            )?
+            .with_sample_policy(cli.sample_policy);
             bgen_stream.read_offset_and_header()?;
             bgen_stream.collect_filters(filter_args_list.filter_args)?;
             let mut writer = BufWriter::new(std::io::stdout());
-            let var_output = match filter_args_list.variant_output {
-                Some(var_out) => var_out,
-                None => VariantOutput::default(),
-            };
-            write_header(&mut writer, &var_output)?;
-            //let line_header = b"alternate_ids\trsid\tchromosome\tposition\tnumber_of_alleles\tfirst_allele\talternative_alleles\n";
-            //writer.write_all(line_header)?;
-            bgen_stream
-                .try_for_each(|variant_data| variant_data?.print(&mut writer, &var_output))?
+            list_writer::write_list(&mut writer, bgen_stream, &var_output)?;
+            writer.flush()?;
         }
         Command::Vcf(list_args_named) => {
-            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, true)?;
+            let mut bgen_stream = BgenStream::from_input(&cli.filename, cli.use_sample_file, true)?
+                .with_sample_policy(cli.sample_policy)
+                .with_chr_renamer(list_args_named.chr_rename.renamer()?);
             bgen_stream.read_offset_and_header()?;
             bgen_stream.collect_filters(list_args_named.filter_args)?;
             vcf_writer::write_vcf(&list_args_named.name, bgen_stream)?;
         }
-        Command::Bgen(list_args_named) => {
-            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, true)?;
+        Command::Bgen(bgen_args) => {
+            let list_args_named = bgen_args.filter_args_named;
+            let mut bgen_stream = BgenStream::from_input(&cli.filename, cli.use_sample_file, true)?
+                .with_sample_policy(cli.sample_policy)
+                .with_chr_renamer(list_args_named.chr_rename.renamer()?)
+                .with_output_layout(bgen_args.layout)
+                .with_output_bits(bgen_args.bits);
+            bgen_stream.read_offset_and_header()?;
+            bgen_stream.collect_filters(list_args_named.filter_args)?;
+            if let Some(error) = bgen_stream.to_bgen(&list_args_named.name, false)? {
+                eprintln!(
+                    "Maximum dosage error after re-quantisation: {}",
+                    error.max_dosage_error
+                );
+                if error.num_not_biallelic > 0 {
+                    eprintln!(
+                        "{} variants that are not biallelic are left out of the dosage error",
+                        error.num_not_biallelic
+                    );
+                }
+            }
+        }
+        Command::Stats(list_args_named) => {
+            let mut bgen_stream = BgenStream::from_input(&cli.filename, cli.use_sample_file, true)?
+                .with_sample_policy(cli.sample_policy);
             bgen_stream.read_offset_and_header()?;
             bgen_stream.collect_filters(list_args_named.filter_args)?;
-            bgen_stream.to_bgen(&list_args_named.name, false)?;
+            stats::write_stats(&list_args_named.name, bgen_stream)?;
+        }
+        Command::Sort(sort_args) => {
+            let chr_order = match sort_args.chr_order {
+                Some(chr_list) => ChrOrder::from_list(&chr_list),
+                None => ChrOrder::Natural,
+            };
+            match sort_args.name {
+                Some(name) if !sort_args.check => {
+                    sort::sort_bgen(&cli.filename, &name, &chr_order, sort_args.max_in_memory)?
+                }
+                _ => {
+                    let mut bgen_stream = BgenStream::from_path(&cli.filename, false, false)?;
+                    bgen_stream.read_offset_and_header()?;
+                    if sort::is_sorted(bgen_stream, &chr_order)? {
+                        println!("{} is sorted", cli.filename);
+                    } else {
+                        println!("{} is not sorted", cli.filename);
+                    }
+                }
+            }
+        }
+        Command::Split(split_args) => {
+            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, false)?
+                .with_sample_policy(cli.sample_policy)
+                .with_chr_renamer(split_args.chr_rename.renamer()?);
+            bgen_stream.read_offset_and_header()?;
+            bgen_stream.collect_filters(split_args.filter_args)?;
+            let output_paths = split::split_bgen(
+                bgen_stream,
+                &split_args.prefix,
+                split_args.by,
+                split_args.chunks.unwrap_or(1),
+                split_args.index,
+            )?;
+            output_paths.iter().for_each(|path| println!("{}", path));
+        }
+        Command::Chunks(chunks_args) => {
+            let locations = chunks::read_locations(&cli.filename)?;
+            let chunk_size = chunks_args.chunk_size.chunk_size()?;
+            let chunks = chunks::plan_chunks(&locations, chunk_size, chunks_args.regions);
+            let writer = BufWriter::new(std::io::stdout());
+            chunks::write_chunks(writer, &chunks, chunks_args.regions)?;
+        }
+        Command::Append(append_args) => {
+            let mut bgen_stream = BgenStream::from_path(&cli.filename, cli.use_sample_file, false)?
+                .with_sample_policy(cli.sample_policy)
+                .with_chr_renamer(append_args.chr_rename.renamer()?);
+            bgen_stream.read_offset_and_header()?;
+            bgen_stream.collect_filters(append_args.filter_args)?;
+            let num_appended = append::append_bgen(&append_args.target, bgen_stream)?;
+            println!(
+                "{} variants appended to {}",
+                num_appended, append_args.target
+            );
+        }
+        Command::Reheader(reheader_args) => {
+            let sample_rewrite = reheader_args.sample_rewrite.sample_rewrite()?;
+            reheader::reheader_bgen(&cli.filename, &reheader_args.name, &sample_rewrite)?;
+        }
+        Command::FreeData(free_data_args) => {
+            match (free_data_args.free_data()?, free_data_args.name) {
+                (Some(free_data), Some(name)) => {
+                    reheader::set_free_data(&cli.filename, &name, free_data)?
+                }
+                _ => {
+                    let mut bgen_stream = BgenStream::from_path(&cli.filename, false, false)?;
+                    bgen_stream.read_offset_and_header()?;
+                    std::io::stdout().write_all(&bgen_stream.header.free_data)?;
+                }
+            }
+        }
+        Command::Info(info_args) => {
+            let mut bgen_stream =
+                BgenStream::from_input(&cli.filename, cli.use_sample_file, false)?
+                    .with_sample_policy(cli.sample_policy);
+            bgen_stream.read_offset_and_header()?;
+            let bgen_info = BgenInfo::from_stream(bgen_stream, info_args.samples)?;
+            let mut writer = BufWriter::new(std::io::stdout());
+            bgen_info.write(&mut writer, info_args.format)?;
+            writer.flush()?;
+        }
+        #[cfg(feature = "arrow")]
+        Command::Export(export_args) => {
+            use bgen_reader::bgen::arrow_export::{ArrowExporter, SampleColumns};
+            let read_data_block = export_args.sample_columns != SampleColumns::None;
+            let mut bgen_stream =
+                BgenStream::from_input(&cli.filename, cli.use_sample_file, read_data_block)?
+                    .with_sample_policy(cli.sample_policy);
+            bgen_stream.read_offset_and_header()?;
+            bgen_stream.collect_filters(export_args.filter_args_named.filter_args)?;
+            let writer =
+                BufWriter::new(std::fs::File::create(&export_args.filter_args_named.name)?);
+            ArrowExporter::new(export_args.format)
+                .with_sample_columns(export_args.sample_columns)
+                .with_row_group_size(export_args.row_group_size as usize)
+                .export(writer, bgen_stream)?;
         }
         Command::Merge(merge_filename) => {
             bgen_merge(
                 merge_filename.name,
                 merge_filename.output_name,
                 cli.filename,
+                merge_filename.chr_rename.renamer()?,
             )?;
         }
     }
@@ -1,12 +1,22 @@
-use clap::error::ErrorKind;
-use clap::CommandFactory;
+#[cfg(feature = "arrow")]
+use crate::bgen::arrow_export::{ArrowExporter, ExportFormat, SampleColumns};
+use crate::bgen::chromosome::{canonical_chr, ChrConvention, ChrRenamer};
+use crate::bgen::chunks::ChunkSize;
+use crate::bgen::info::InfoFormat;
+use crate::bgen::reheader::SampleRewrite;
+use crate::bgen::samples::{read_sample_file, read_sample_mapping, SamplePolicy};
+use crate::bgen::split::SplitMode;
+use crate::bgen::stats::VariantStats;
+use crate::list_writer::ListField;
 use clap::{Args, Parser, Subcommand};
 use color_eyre::Report;
 use color_eyre::Result;
+use itertools::Itertools;
 
 #[derive(Parser)]
 pub struct Cli {
-    /// Bgen file name
+    /// Bgen file name, `-` for the standard input of the list, vcf, stats and info commands,
+    /// and of the bgen command when it writes to a file
     #[arg(short, long, value_name = "FILE")]
     pub filename: String,
 
@@ -18,6 +28,10 @@ pub struct Cli {
     #[arg(short, long, default_value_t = false)]
     pub use_sample_file: bool,
 
+    /// Which sample identifiers to use when the bgen file and the .sample file both have them
+    #[arg(long, value_enum, default_value_t = SamplePolicy::RequireMatch)]
+    pub sample_policy: SamplePolicy,
+
     /// What command to run
     #[command(subcommand)]
     pub command: Command,
@@ -32,21 +46,231 @@ pub enum Command {
     /// output VCF information
     Vcf(FilterArgsNamed),
     /// Output Bgen information
-    Bgen(FilterArgsNamed),
+    Bgen(BgenArgs),
     /// Merge multiple bgen files together
     Merge(MergeArgs),
+    /// Output allele frequency, HWE and imputation quality statistics for each variant
+    Stats(FilterArgsNamed),
+    /// Sort the variants by chromosome, position and alleles
+    Sort(SortArgs),
+    /// Split the file per chromosome or in chunks
+    Split(SplitArgs),
+    /// Print chunks of the file, as byte offsets or regions, without splitting it
+    Chunks(ChunksArgs),
+    /// Append the variants of the file at the end of an existing bgen file
+    Append(AppendArgs),
+    /// Rewrite, add or remove the sample identifiers of the file
+    Reheader(ReheaderArgs),
+    /// Print or set the free data area of the header
+    FreeData(FreeDataArgs),
+    /// Print the header, the samples and the chromosomes of the file
+    Info(InfoArgs),
+    /// Export the variants, and optionally their dosages or probabilities, as Arrow IPC or
+    /// Parquet
+    #[cfg(feature = "arrow")]
+    Export(ExportArgs),
 }
 #[derive(Parser, Default)]
 pub struct MergeArgs {
+    #[command(flatten)]
+    pub chr_rename: ChrRenameArgs,
     pub name: String,
     pub output_name: String,
 }
 #[derive(Parser, Default)]
+pub struct SortArgs {
+    #[arg(long)]
+    /// Optional comma separated order of the chromosomes, e.g. --chr-order 1,2,X
+    pub chr_order: Option<String>,
+    #[arg(long, default_value_t = 1_000_000)]
+    /// Number of variants sorted in memory before being written to a chunk file
+    pub max_in_memory: usize,
+    #[arg(long)]
+    /// Only report whether the file is sorted
+    pub check: bool,
+    #[arg(required_unless_present = "check")]
+    pub name: Option<String>,
+}
+#[derive(Parser, Default)]
+pub struct SplitArgs {
+    #[command(flatten)]
+    pub filter_args: FilterArgs,
+    #[command(flatten)]
+    pub chr_rename: ChrRenameArgs,
+    #[arg(long, value_enum, default_value_t = SplitMode::Chromosome)]
+    /// Split per chromosome, or in chunks of about the same number of variants or bytes
+    pub by: SplitMode,
+    #[arg(long, required_if_eq_any([("by", "variants"), ("by", "bytes")]))]
+    /// Number of chunks, when splitting by variants or bytes
+    pub chunks: Option<usize>,
+    #[arg(long)]
+    /// Also index each of the files written
+    pub index: bool,
+    /// Prefix of the files written
+    pub prefix: String,
+}
+#[derive(Parser, Default)]
+pub struct AppendArgs {
+    #[command(flatten)]
+    pub filter_args: FilterArgs,
+    #[command(flatten)]
+    pub chr_rename: ChrRenameArgs,
+    /// Bgen file the variants are appended to
+    pub target: String,
+}
+#[derive(Parser, Default)]
+pub struct InfoArgs {
+    #[arg(long)]
+    /// List the sample identifiers
+    pub samples: bool,
+    #[arg(long, value_enum, default_value_t = InfoFormat::Text)]
+    /// Human readable text or JSON
+    pub format: InfoFormat,
+}
+#[cfg(feature = "arrow")]
+#[derive(Parser)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub filter_args_named: FilterArgsNamed,
+    #[arg(long, value_enum, default_value_t = ExportFormat::Ipc)]
+    /// Arrow IPC or Parquet
+    pub format: ExportFormat,
+    #[arg(long, value_enum, default_value_t = SampleColumns::None)]
+    /// Columns written for each sample
+    pub sample_columns: SampleColumns,
+    #[arg(long, default_value_t = ArrowExporter::DEFAULT_ROW_GROUP_SIZE as u64, value_parser = clap::value_parser!(u64).range(1..))]
+    /// Number of variants per record batch or row group
+    pub row_group_size: u64,
+}
+#[derive(Parser, Default)]
+pub struct FreeDataArgs {
+    #[arg(long, requires = "name")]
+    /// Text to write in the free data area
+    pub set: Option<String>,
+    #[arg(long, requires = "name", conflicts_with = "set")]
+    /// File whose content is written in the free data area
+    pub set_file: Option<String>,
+    #[arg(long, requires = "name", conflicts_with_all = ["set", "set_file"])]
+    /// Empty the free data area
+    pub clear: bool,
+    /// Output file, when the free data area is changed
+    pub name: Option<String>,
+}
+impl FreeDataArgs {
+    /// New content of the free data area, if it is changed
+    pub fn free_data(&self) -> Result<Option<Vec<u8>>> {
+        match (&self.set, &self.set_file) {
+            (Some(text), _) => Ok(Some(text.clone().into_bytes())),
+            (_, Some(path)) => Ok(Some(std::fs::read(path)?)),
+            _ if self.clear => Ok(Some(Vec::new())),
+            _ => Ok(None),
+        }
+    }
+}
+#[derive(Parser, Default)]
+pub struct ReheaderArgs {
+    #[command(flatten)]
+    pub sample_rewrite: SampleRewriteArgs,
+    pub name: String,
+}
+#[derive(Args, Default)]
+#[group(required = true, multiple = false)]
+pub struct SampleRewriteArgs {
+    #[arg(long)]
+    /// Mapping file with the old and the new identifier of a sample on each line
+    pub sample_map: Option<String>,
+    #[arg(long)]
+    /// .sample file whose ID_1 column replaces the identifiers
+    pub sample_file: Option<String>,
+    #[arg(long)]
+    /// Remove the embedded sample identifiers
+    pub remove_samples: bool,
+}
+impl SampleRewriteArgs {
+    pub fn sample_rewrite(&self) -> Result<SampleRewrite> {
+        match (&self.sample_map, &self.sample_file) {
+            (Some(sample_map), _) => Ok(SampleRewrite::Mapping(read_sample_mapping(sample_map)?)),
+            (_, Some(sample_file)) => Ok(SampleRewrite::Replace(
+                read_sample_file(sample_file)?
+                    .into_iter()
+                    .map(|entry| entry.id_1)
+                    .collect(),
+            )),
+            _ => Ok(SampleRewrite::Remove),
+        }
+    }
+}
+#[derive(Parser, Default)]
+pub struct ChunksArgs {
+    #[command(flatten)]
+    pub chunk_size: ChunkSizeArgs,
+    #[arg(long)]
+    /// Print one region per line, chunks are then cut at each new chromosome
+    pub regions: bool,
+}
+#[derive(Args, Default)]
+#[group(required = true, multiple = false)]
+pub struct ChunkSizeArgs {
+    #[arg(long)]
+    /// Maximum number of variants in a chunk
+    pub variants: Option<usize>,
+    #[arg(long)]
+    /// Maximum number of bytes in a chunk
+    pub bytes: Option<u64>,
+}
+impl ChunkSizeArgs {
+    pub fn chunk_size(&self) -> Result<ChunkSize> {
+        match (self.variants, self.bytes) {
+            (Some(variants), _) => Ok(ChunkSize::Variants(variants.max(1))),
+            (_, Some(bytes)) => Ok(ChunkSize::Bytes(bytes)),
+            _ => Err(Report::msg("Either --variants or --bytes is needed")),
+        }
+    }
+}
+#[derive(Parser, Default)]
+pub struct BgenArgs {
+    #[command(flatten)]
+    pub filter_args_named: FilterArgsNamed,
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=2))]
+    /// Layout of the output, 1 for bgen v1.1 or 2 for bgen v1.2 and v1.3. Layout 1 only
+    /// holds unphased, biallelic and diploid data
+    pub layout: Option<u8>,
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=32))]
+    /// Number of bits of the probabilities in layout 2. Probabilities are re-quantised and
+    /// the largest change of a dosage is reported, along with the number of variants that
+    /// are not biallelic and have no dosage
+    pub bits: Option<u8>,
+}
+#[derive(Parser, Default)]
 pub struct FilterArgsNamed {
     #[command(flatten)]
     pub filter_args: FilterArgs,
+    #[command(flatten)]
+    pub chr_rename: ChrRenameArgs,
+    /// Output file, `-` for the standard output
     pub name: String,
 }
+
+#[derive(Args, Default)]
+#[group(required = false, multiple = false)]
+pub struct ChrRenameArgs {
+    #[arg(long, value_enum)]
+    /// Optional naming convention for the chromosomes of the output
+    pub chr_convention: Option<ChrConvention>,
+    #[arg(long)]
+    /// Optional chromosome mapping file, one old and new name per line
+    pub chr_map: Option<String>,
+}
+
+impl ChrRenameArgs {
+    pub fn renamer(&self) -> Result<Option<ChrRenamer>> {
+        match (&self.chr_convention, &self.chr_map) {
+            (Some(convention), _) => Ok(Some(ChrRenamer::Convention(*convention))),
+            (None, Some(chr_map)) => Ok(Some(ChrRenamer::from_mapping_file(chr_map)?)),
+            (None, None) => Ok(None),
+        }
+    }
+}
 #[derive(Parser, Default)]
 pub struct FilterArgsList {
     #[command(flatten)]
@@ -60,6 +284,29 @@ pub enum VariantOutput {
     Bgenix,
     #[default]
     Rsid,
+    /// One JSON object per line with the selected fields
+    Json(ListFieldsArgs),
+    /// Tab separated values with a header line and the selected fields
+    Tsv(ListFieldsArgs),
+}
+
+impl VariantOutput {
+    /// Statistics are selected, which are computed from the data blocks
+    pub fn needs_data_block(&self) -> bool {
+        match self {
+            VariantOutput::Json(fields_args) | VariantOutput::Tsv(fields_args) => {
+                fields_args.fields.iter().any(|field| field.is_stat())
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+pub struct ListFieldsArgs {
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = ListField::DEFAULT_FIELDS)]
+    /// Comma separated fields of each variant, in output order
+    pub fields: Vec<ListField>,
 }
 
 #[derive(Parser, Default)]
@@ -72,9 +319,27 @@ pub struct FilterArgs {
     pub incl_rsid: InclRsid,
     #[command(flatten)]
     pub excl_rsid: ExclRsid,
+    #[command(flatten)]
+    pub incl_variant_id: InclVariantId,
+    #[command(flatten)]
+    pub excl_variant_id: ExclVariantId,
+    #[command(flatten)]
+    pub incl_variant_key: InclVariantKey,
+    #[command(flatten)]
+    pub excl_variant_key: ExclVariantKey,
+    #[arg(long)]
+    /// Match chr:pos:ref:alt keys regardless of the order of the alleles
+    pub unordered_alleles: bool,
+    #[arg(long)]
+    /// The file is sorted by position, stop reading once past the last included range
+    pub assume_sorted: bool,
+    #[command(flatten)]
+    pub stat_filters: StatFilters,
+    #[command(flatten)]
+    pub allele_filters: AlleleFilters,
 }
-// TODO(lhenches): this should be Hashmap for rsids
 type AllFilters = (Vec<Range>, Vec<String>, Vec<Range>, Vec<String>);
+type IdFilters = (Vec<String>, Vec<String>, Vec<String>, Vec<String>);
 
 impl FilterArgs {
     pub fn with_range_incl_file(mut self, incl_file_str: String) -> Self {
@@ -125,53 +390,101 @@ impl FilterArgs {
         self
     }
 
-    pub fn get_vector_incl_and_excl(&self) -> Result<AllFilters> {
-        let opt_incl_range = match &self.incl_range {
-            InclRange {
-                incl_range,
-                incl_range_file: None,
-            } => incl_range.clone(),
-            InclRange {
-                incl_range: None,
-                incl_range_file,
-            } => Some(std::fs::read_to_string(
-                incl_range_file
-                    .clone()
-                    .ok_or(Report::msg("Range file does not exist"))?,
-            )?),
-            _ => panic!("Range file and range at command line specified"),
+    pub fn with_stat_filters(mut self, stat_filters: StatFilters) -> Self {
+        self.stat_filters = stat_filters;
+        self
+    }
+
+    pub fn with_allele_filters(mut self, allele_filters: AlleleFilters) -> Self {
+        self.allele_filters = allele_filters;
+        self
+    }
+
+    pub fn with_variant_id_incl_str(mut self, incl_str: String) -> Self {
+        self.incl_variant_id = InclVariantId {
+            incl_variant_id: Some(incl_str),
+            incl_variant_id_file: None,
         };
-        let vec_incl_range = if let Some(incl_range_string) = opt_incl_range {
-            match validate_parsing_range(incl_range_string) {
-                Ok(range) => range,
-                Err(cmd_error) => cmd_error.exit(),
-            }
-        } else {
-            Vec::new()
+        self
+    }
+
+    pub fn with_variant_id_excl_str(mut self, excl_str: String) -> Self {
+        self.excl_variant_id = ExclVariantId {
+            excl_variant_id: Some(excl_str),
+            excl_variant_id_file: None,
+        };
+        self
+    }
+
+    pub fn with_variant_key_incl_str(mut self, incl_str: String) -> Self {
+        self.incl_variant_key = InclVariantKey {
+            incl_variant_key: Some(incl_str),
+            incl_variant_key_file: None,
         };
-        let opt_excl_range = match &self.excl_range {
-            ExclRange {
-                excl_range,
-                excl_range_file: None,
-            } => excl_range.clone(),
-            ExclRange {
-                excl_range: None,
-                excl_range_file,
-            } => Some(std::fs::read_to_string(
-                excl_range_file
-                    .clone()
-                    .ok_or(Report::msg("Range file does not exist"))?,
-            )?),
-            _ => panic!("Range file and range at command line specified"),
+        self
+    }
+
+    pub fn with_variant_key_excl_file(mut self, excl_file_str: String) -> Self {
+        self.excl_variant_key = ExclVariantKey {
+            excl_variant_key: None,
+            excl_variant_key_file: Some(excl_file_str),
         };
-        let vec_excl_range = if let Some(excl_range_string) = opt_excl_range {
-            match validate_parsing_range(excl_range_string) {
-                Ok(range) => range,
-                Err(cmd_error) => cmd_error.exit(),
-            }
-        } else {
-            Vec::new()
+        self
+    }
+
+    pub fn with_assume_sorted(mut self, assume_sorted: bool) -> Self {
+        self.assume_sorted = assume_sorted;
+        self
+    }
+
+    pub fn with_unordered_alleles(mut self, unordered_alleles: bool) -> Self {
+        self.unordered_alleles = unordered_alleles;
+        self
+    }
+
+    /// Included and excluded alternate ids, then included and excluded normalised
+    /// chr:pos:ref:alt keys
+    pub fn get_variant_ids_incl_and_excl(&self) -> Result<IdFilters> {
+        let vec_incl_id = read_id_list(
+            &self.incl_variant_id.incl_variant_id,
+            &self.incl_variant_id.incl_variant_id_file,
+        )?;
+        let vec_excl_id = read_id_list(
+            &self.excl_variant_id.excl_variant_id,
+            &self.excl_variant_id.excl_variant_id_file,
+        )?;
+        let normalise_keys = |keys: Vec<String>| -> Result<Vec<String>> {
+            keys.iter()
+                .map(|key| {
+                    normalise_variant_key(key, self.unordered_alleles).ok_or(Report::msg(format!(
+                        "Invalid variant key {}. Please use the chr:pos:ref:alt format",
+                        key
+                    )))
+                })
+                .collect()
         };
+        let vec_incl_key = normalise_keys(read_id_list(
+            &self.incl_variant_key.incl_variant_key,
+            &self.incl_variant_key.incl_variant_key_file,
+        )?)?;
+        let vec_excl_key = normalise_keys(read_id_list(
+            &self.excl_variant_key.excl_variant_key,
+            &self.excl_variant_key.excl_variant_key_file,
+        )?)?;
+        Ok((vec_incl_id, vec_excl_id, vec_incl_key, vec_excl_key))
+    }
+
+    pub fn get_vector_incl_and_excl(&self) -> Result<AllFilters> {
+        let vec_incl_range = read_ranges(
+            &self.incl_range.incl_range,
+            &self.incl_range.incl_range_file,
+            true,
+        )?;
+        let vec_excl_range = read_ranges(
+            &self.excl_range.excl_range,
+            &self.excl_range.excl_range_file,
+            false,
+        )?;
         let opt_incl_rsid: Vec<String> = match &self.incl_rsid {
             InclRsid {
                 incl_rsid,
@@ -210,10 +523,10 @@ impl FilterArgs {
 #[group(required = false, multiple = false)]
 pub struct InclRange {
     #[arg(long)]
-    /// Optional range in the format --incl-range 1:0-10000
+    /// Optional comma separated ranges in the format --incl-range 1:0-10000,2:500-,X
     pub incl_range: Option<String>,
     #[arg(long)]
-    /// Optional range file, one range per line
+    /// Optional range file, one range per line, or a BED file with a .bed extension
     pub incl_range_file: Option<String>,
 }
 
@@ -221,10 +534,10 @@ pub struct InclRange {
 #[group(required = false, multiple = false)]
 pub struct ExclRange {
     #[arg(long)]
-    /// Optional range in the format --excl-range 1:0-10000
+    /// Optional comma separated ranges in the format --excl-range 1:0-10000,2:500-,X
     pub excl_range: Option<String>,
     #[arg(long)]
-    /// Optional range file, one range per line
+    /// Optional range file, one range per line, or a BED file with a .bed extension
     pub excl_range_file: Option<String>,
 }
 
@@ -250,15 +563,288 @@ pub struct ExclRsid {
     pub excl_rsid_file: Option<String>,
 }
 
-pub fn validate_parsing_range(incl_range: String) -> Result<Vec<Range>, clap::error::Error> {
-    incl_range
-        .trim_end_matches('\n')
-        .split('\n')
-        .map(|v| Range::from_str(v, true))
+#[derive(Args, Default)]
+#[group(required = false, multiple = false)]
+pub struct InclVariantId {
+    #[arg(long)]
+    /// Optional alternate variant id in the format --incl-variant-id id100
+    pub incl_variant_id: Option<String>,
+    #[arg(long)]
+    /// Optional alternate variant id file, one id per line
+    pub incl_variant_id_file: Option<String>,
+}
+
+#[derive(Args, Default)]
+#[group(required = false, multiple = false)]
+pub struct ExclVariantId {
+    #[arg(long)]
+    /// Optional alternate variant id in the format --excl-variant-id id100
+    pub excl_variant_id: Option<String>,
+    #[arg(long)]
+    /// Optional alternate variant id file, one id per line
+    pub excl_variant_id_file: Option<String>,
+}
+
+#[derive(Args, Default)]
+#[group(required = false, multiple = false)]
+pub struct InclVariantKey {
+    #[arg(long)]
+    /// Optional variant in the format --incl-variant-key 1:752566:G:A
+    pub incl_variant_key: Option<String>,
+    #[arg(long)]
+    /// Optional chr:pos:ref:alt file, one variant per line
+    pub incl_variant_key_file: Option<String>,
+}
+
+#[derive(Args, Default)]
+#[group(required = false, multiple = false)]
+pub struct ExclVariantKey {
+    #[arg(long)]
+    /// Optional variant in the format --excl-variant-key 1:752566:G:A
+    pub excl_variant_key: Option<String>,
+    #[arg(long)]
+    /// Optional chr:pos:ref:alt file, one variant per line
+    pub excl_variant_key_file: Option<String>,
+}
+
+fn read_id_list(value: &Option<String>, file: &Option<String>) -> Result<Vec<String>> {
+    let content = match (value, file) {
+        (Some(value), _) => value.clone(),
+        (None, Some(file)) => std::fs::read_to_string(file)?,
+        (None, None) => return Ok(Vec::new()),
+    };
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// Builds the key used to compare variants, `chr:pos:alleles` with upper case alleles and
+/// the canonical chromosome name. `_` is accepted as a separator when the key contains no `:`,
+/// in which case the position is the last number followed by at least two alleles, so that
+/// contigs such as `chrUn_gl000220` keep their `_`.
+/// With `unordered_alleles`, the alleles are sorted so that `G:A` and `A:G` are equal.
+///
+/// # Examples
+/// ```
+/// # use bgen_reader::parser::normalise_variant_key;
+/// assert_eq!(normalise_variant_key("chr1:752566:g:a", false).unwrap(), "1:752566:G:A");
+/// assert_eq!(normalise_variant_key("1_752566_G_A", true).unwrap(), "1:752566:A:G");
+/// assert_eq!(
+///     normalise_variant_key("chrUn_gl000220_100_A_G", false),
+///     normalise_variant_key("chrUn_gl000220:100:A:G", false)
+/// );
+/// assert!(normalise_variant_key("1:752566", false).is_none());
+/// ```
+pub fn normalise_variant_key(key: &str, unordered_alleles: bool) -> Option<String> {
+    let separator = if key.contains(':') { ':' } else { '_' };
+    let fields = key.trim().split(separator).collect_vec();
+    let pos_index = if separator == ':' {
+        1
+    } else {
+        (1..fields.len().saturating_sub(2))
+            .rev()
+            .find(|&i| fields[i].parse::<u32>().is_ok())?
+    };
+    let chr = fields[..pos_index].join(&separator.to_string());
+    let pos = fields.get(pos_index)?.parse::<u32>().ok()?;
+    let alleles = fields[pos_index + 1..]
+        .iter()
+        .map(|a| a.to_ascii_uppercase())
+        .collect_vec();
+    if chr.is_empty() || alleles.len() < 2 {
+        return None;
+    }
+    Some(variant_key(&chr, pos, alleles, unordered_alleles))
+}
+
+pub fn variant_key(
+    chr: &str,
+    pos: u32,
+    mut alleles: Vec<String>,
+    unordered_alleles: bool,
+) -> String {
+    let chr = canonical_chr(chr);
+    if unordered_alleles {
+        alleles.sort();
+    }
+    format!("{}:{}:{}", chr, pos, alleles.join(":"))
+}
+
+/// Filters evaluated on the statistics of the decoded data block, see `VariantStats`
+#[derive(Args, Default, Clone, Debug, PartialEq)]
+pub struct StatFilters {
+    #[arg(long)]
+    /// Optional minimum minor allele frequency
+    pub min_maf: Option<f64>,
+    #[arg(long)]
+    /// Optional maximum minor allele frequency
+    pub max_maf: Option<f64>,
+    #[arg(long)]
+    /// Optional minimum IMPUTE info score
+    pub min_info: Option<f64>,
+    #[arg(long)]
+    /// Optional maximum proportion of missing samples
+    pub max_missing: Option<f64>,
+    #[arg(long)]
+    /// Optional Hardy-Weinberg exact test threshold, variants with a lower p-value are excluded
+    pub hwe_p: Option<f64>,
+}
+
+impl StatFilters {
+    pub fn is_empty(&self) -> bool {
+        *self == StatFilters::default()
+    }
+
+    /// Statistics that are undefined (NaN) never pass a threshold
+    pub fn accept(&self, stats: &VariantStats) -> bool {
+        let at_least = |value: f64, min: Option<f64>| min.is_none_or(|min| value >= min);
+        let at_most = |value: f64, max: Option<f64>| max.is_none_or(|max| value <= max);
+        at_least(stats.minor_allele_frequency, self.min_maf)
+            && at_most(stats.minor_allele_frequency, self.max_maf)
+            && at_least(stats.impute_info, self.min_info)
+            && at_most(stats.missing_proportion, self.max_missing)
+            && at_least(stats.hwe_p_value, self.hwe_p)
+    }
+}
+
+/// Filters evaluated on the alleles of a variant, before its data block is decoded
+#[derive(Args, Default, Clone, Debug, PartialEq)]
+pub struct AlleleFilters {
+    #[arg(long)]
+    /// Only keep variants with exactly two alleles
+    pub biallelic: bool,
+    #[arg(long)]
+    /// Optional maximum number of alleles
+    pub max_alleles: Option<u16>,
+    #[arg(long, conflicts_with = "indels_only")]
+    /// Only keep variants where all alleles are a single base
+    pub snvs_only: bool,
+    #[arg(long)]
+    /// Only keep variants where alleles differ in length
+    pub indels_only: bool,
+    #[arg(long)]
+    /// Exclude strand ambiguous A/T and C/G SNPs
+    pub exclude_ambiguous: bool,
+}
+
+impl AlleleFilters {
+    pub fn is_empty(&self) -> bool {
+        *self == AlleleFilters::default()
+    }
+
+    pub fn accept(&self, alleles: &[String]) -> bool {
+        let is_snv = alleles.iter().all(|a| a.len() == 1);
+        let is_indel = alleles.iter().map(|a| a.len()).all_equal_value().is_err();
+        !(self.biallelic && alleles.len() != 2
+            || self
+                .max_alleles
+                .is_some_and(|max| alleles.len() > max as usize)
+            || self.snvs_only && !is_snv
+            || self.indels_only && !is_indel
+            || self.exclude_ambiguous && is_strand_ambiguous(alleles))
+    }
+}
+
+/// # Examples
+/// ```
+/// # use bgen_reader::parser::is_strand_ambiguous;
+/// assert!(is_strand_ambiguous(&["A".to_string(), "T".to_string()]));
+/// assert!(!is_strand_ambiguous(&["A".to_string(), "G".to_string()]));
+/// ```
+pub fn is_strand_ambiguous(alleles: &[String]) -> bool {
+    match alleles {
+        [a, b] => matches!(
+            (
+                a.to_ascii_uppercase().as_str(),
+                b.to_ascii_uppercase().as_str()
+            ),
+            ("A", "T") | ("T", "A") | ("C", "G") | ("G", "C")
+        ),
+        _ => false,
+    }
+}
+
+/// Parses ranges given one per line, or separated by commas. Empty lines and lines starting
+/// with `#` are ignored.
+pub fn validate_parsing_range(ranges: &str, incl: bool) -> Result<Vec<Range>> {
+    ranges
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .flat_map(|(i, line)| line.split(',').map(move |range| (i, range)))
+        .map(|(i, range)| {
+            Range::from_str(range, incl).map_err(|e| Report::msg(format!("Line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+/// Parses a UCSC BED file, with 0-based half-open intervals. Columns after the third one
+/// are ignored, as well as `track`, `browser` and `#` header lines.
+pub fn parse_bed(bed: &str, incl: bool) -> Result<Vec<Range>> {
+    bed.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            !(line.trim().is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser"))
+        })
+        .map(|(i, line)| {
+            let err = |reason: &str| {
+                Report::msg(format!("Line {} of BED file `{}`: {}", i + 1, line, reason))
+            };
+            let mut columns = line.split_whitespace();
+            let chr = columns.next().ok_or_else(|| err("missing chromosome"))?;
+            let mut coordinate = |name: &str| {
+                columns
+                    .next()
+                    .ok_or_else(|| err(&format!("missing {} column", name)))?
+                    .parse::<u32>()
+                    .map_err(|_| err(&format!("{} is not a positive integer", name)))
+            };
+            let start = coordinate("start")?;
+            let end = coordinate("end")?;
+            if end <= start {
+                return Err(err("end is not after start"));
+            }
+            Ok(Range {
+                chr: chr.to_string(),
+                start: start + 1,
+                end,
+                incl,
+            })
+        })
         .collect()
 }
 
+fn read_ranges(
+    range: &Option<String>,
+    range_file: &Option<String>,
+    incl: bool,
+) -> Result<Vec<Range>> {
+    match (range, range_file) {
+        (Some(range), _) => validate_parsing_range(range, incl),
+        (None, Some(range_file)) => {
+            let content = std::fs::read_to_string(range_file).map_err(|e| {
+                Report::msg(format!("Cannot read range file {}: {}", range_file, e))
+            })?;
+            let ranges = if range_file.ends_with(".bed") {
+                parse_bed(&content, incl)
+            } else {
+                validate_parsing_range(&content, incl)
+            };
+            ranges.map_err(|e| Report::msg(format!("In range file {}. {}", range_file, e)))
+        }
+        (None, None) => Ok(Vec::new()),
+    }
+}
+
+/// Range of positions on a chromosome, 1-based and inclusive
 #[derive(Clone, Args, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Range {
     pub chr: String,
     pub start: u32,
@@ -267,30 +853,57 @@ pub struct Range {
 }
 
 impl Range {
-    fn from_str(s: &str, incl: bool) -> Result<Self, clap::error::Error> {
-        let err = || {
-            let mut cmd = Cli::command();
-            cmd.error(
-                ErrorKind::ValueValidation,
-                "Invalid range format. Please use the following format: \n\
-                bgen_reader -f file.bgen list --incl-range 1:0-10000 \
-                ",
-            )
+    /// Parses `chr:start-end`. The start or the end can be left out for open-ended ranges,
+    /// `chr:pos` is a single position and `chr` alone the whole chromosome.
+    ///
+    /// # Examples
+    /// ```
+    /// # use bgen_reader::parser::Range;
+    /// let range = Range::from_str("1:1000000-", true).unwrap();
+    /// assert_eq!((range.start, range.end), (1000000, u32::MAX));
+    /// let range = Range::from_str("22", true).unwrap();
+    /// assert_eq!((range.chr.as_str(), range.start), ("22", 0));
+    /// assert!(Range::from_str("1:10-a", true).is_err());
+    /// ```
+    pub fn from_str(s: &str, incl: bool) -> Result<Self> {
+        let s = s.trim();
+        let err = |reason: &str| {
+            Report::msg(format!(
+                "Invalid range `{}`, {}. Please use the format chr:start-end, \
+                chr:start-, chr:pos or chr",
+                s, reason
+            ))
+        };
+        let position = |p: &str| {
+            p.parse::<u32>()
+                .map_err(|_| err(&format!("{} is not a positive integer", p)))
+        };
+        let (chr, coordinates) = match s.split_once(':') {
+            Some((chr, coordinates)) => (chr, Some(coordinates)),
+            None => (s, None),
+        };
+        if chr.is_empty() {
+            return Err(err("the chromosome is missing"));
+        }
+        let (start, end) = match coordinates.map(|c| (c, c.split_once('-'))) {
+            None => (0, u32::MAX),
+            Some((_, Some((start, end)))) => (
+                if start.is_empty() {
+                    0
+                } else {
+                    position(start)?
+                },
+                if end.is_empty() {
+                    u32::MAX
+                } else {
+                    position(end)?
+                },
+            ),
+            Some((pos, None)) => (position(pos)?, position(pos)?),
         };
-        let mut split_expr = s.split(':');
-        let chr = split_expr.next().ok_or(err())?;
-        let expr_2 = split_expr.next().ok_or(err())?;
-        let mut range_split = expr_2.split('-');
-        let start = range_split
-            .next()
-            .ok_or(err())?
-            .parse::<u32>()
-            .map_err(|_| err())?;
-        let end = range_split
-            .next()
-            .ok_or(err())?
-            .parse::<u32>()
-            .map_err(|_| err())?;
+        if start > end {
+            return Err(err("the start is after the end"));
+        }
         Ok(Range {
             chr: chr.to_string(),
             start,
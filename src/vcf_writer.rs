@@ -1,7 +1,7 @@
 use crate::bgen::bgen_stream::BgenStream;
+use crate::bgen::utils::create_output;
 use color_eyre::Result;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 
 const HEADER_LINES: &[&str] = &[
     "##fileformat=VCFv4.2\n",
@@ -10,9 +10,19 @@ const HEADER_LINES: &[&str] = &[
     "##FORMAT=<ID=HP,Type=Float,Number=.,Description=\"Haplotype call probabilities\">\n",
 ];
 
+/// Writes the stream as a VCF file, `-` stands for the standard output
 pub fn write_vcf<T: std::io::Read>(output_path: &str, bgen_stream: BgenStream<T>) -> Result<()> {
-    let file = File::create(output_path)?;
-    let mut writer = BufWriter::new(file);
+    let mut writer = create_output(output_path)?;
+    write_vcf_to(&mut writer, bgen_stream)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the stream in the VCF format to `writer`
+pub fn write_vcf_to<T: std::io::Read>(
+    mut writer: impl Write,
+    bgen_stream: BgenStream<T>,
+) -> Result<()> {
     for line in HEADER_LINES {
         writer.write_all(line.as_bytes())?;
     }
@@ -0,0 +1,99 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::append::append_bgen;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::bgi_writer::{index_is_current, write_index};
+use bgen_reader::bgen::chunks::read_locations;
+use bgen_reader::parser::FilterArgs;
+use common::{create_bgen_and_read, open_path};
+use tempfile::tempdir;
+
+#[test]
+fn appending_restores_file_and_index() {
+    let dir = tempdir().unwrap();
+    let target = dir.path().join("target.bgen");
+    let target = target.to_str().unwrap();
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream.to_bgen(target, false).unwrap();
+    let mut target_stream = BgenStream::from_path(target, false, false).unwrap();
+    target_stream.read_offset_and_header().unwrap();
+    let num_target = target_stream.header.variant_num;
+    write_index(target_stream).unwrap();
+
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_excl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    let num_appended = append_bgen(target, bgen_stream).unwrap();
+    assert_eq!(100, num_target + num_appended);
+
+    let mut appended = BgenStream::from_path(target, false, true).unwrap();
+    appended.read_offset_and_header().unwrap();
+    assert_eq!(100, appended.header.variant_num);
+    let appended: Vec<_> = appended.map(|r| r.unwrap()).collect();
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    assert_eq!(original.len(), appended.len());
+    for (variant, copy) in original.iter().zip(appended.iter()) {
+        assert_eq!(variant.rsid, copy.rsid);
+        assert_eq!(variant.data_block, copy.data_block);
+    }
+    // the index holds the locations of both the original and the appended variants
+    let indexed = read_locations(target).unwrap();
+    assert_eq!(100, indexed.len());
+    let scanned_locations: Vec<_> = appended
+        .iter()
+        .map(|v| (v.file_start_position as u64, v.size_in_bytes as u64))
+        .collect();
+    let indexed_locations: Vec<_> = indexed
+        .iter()
+        .map(|l| (l.file_start_position, l.size_in_bytes))
+        .collect();
+    assert_eq!(scanned_locations, indexed_locations);
+}
+
+#[test]
+fn appending_updates_bgenix_index() {
+    let dir = tempdir().unwrap();
+    let target = dir.path().join("target.bgen");
+    let target = target.to_str().unwrap();
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream.to_bgen(target, false).unwrap();
+    let mut target_stream = BgenStream::from_path(target, false, false).unwrap();
+    target_stream.read_offset_and_header().unwrap();
+    // bgenix indexes have the same tables
+    let index_path = write_index(target_stream).unwrap();
+    let bgi_path = format!("{}.bgi", target);
+    std::fs::rename(index_path, &bgi_path).unwrap();
+
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_excl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    append_bgen(target, bgen_stream).unwrap();
+    assert!(index_is_current(target, &bgi_path).unwrap());
+    let scanned: Vec<_> = open_path(target)
+        .map(|r| r.unwrap().file_start_position as u64)
+        .collect();
+    let indexed: Vec<_> = read_locations(target)
+        .unwrap()
+        .iter()
+        .map(|l| l.file_start_position)
+        .collect();
+    assert_eq!(scanned, indexed);
+}
+
+#[test]
+fn appending_different_samples_fails() {
+    let dir = tempdir().unwrap();
+    let target = dir.path().join("target.bgen");
+    let target = target.to_str().unwrap();
+    create_bgen_and_read().to_bgen(target, false).unwrap();
+    let size_before = std::fs::metadata(target).unwrap().len();
+    let mut bgen_stream = create_bgen_and_read();
+    bgen_stream.samples[1] = "renamed".to_string();
+    let error = append_bgen(target, bgen_stream).unwrap_err();
+    assert!(error.to_string().starts_with("Sample 2 differs"));
+    assert_eq!(size_before, std::fs::metadata(target).unwrap().len());
+}
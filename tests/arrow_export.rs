@@ -0,0 +1,191 @@
+#![cfg(feature = "arrow")]
+extern crate bgen_reader;
+mod common;
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{Float64Type, UInt32Type};
+use arrow::ipc::reader::FileReader;
+use bgen_reader::bgen::arrow_export::{ArrowExporter, ExportFormat, SampleColumns};
+use bgen_reader::bgen::writer::BgenWriter;
+use common::{create_bgen_and_read, read_bytes};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::fs::File;
+use std::io::Cursor;
+use tempfile::tempdir;
+
+#[test]
+fn ipc_variant_metadata() {
+    let mut ipc = Vec::new();
+    let variant_num = ArrowExporter::new(ExportFormat::Ipc)
+        .with_row_group_size(30)
+        .export(&mut ipc, create_bgen_and_read())
+        .unwrap();
+    assert_eq!(100, variant_num);
+    let reader = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+    assert_eq!(7, reader.schema().fields().len());
+    let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+    let sizes: Vec<_> = batches.iter().map(|b| b.num_rows()).collect();
+    assert_eq!(vec![30, 30, 30, 10], sizes);
+    let first = &batches[0];
+    assert_eq!("1", first["chr"].as_string::<i32>().value(0));
+    assert_eq!(752566, first["pos"].as_primitive::<UInt32Type>().value(0));
+    assert_eq!("1_752566_G_A", first["rsid"].as_string::<i32>().value(0));
+    let alleles = first["alleles"].as_list::<i32>().value(0);
+    let alleles = alleles.as_string::<i32>();
+    assert_eq!(vec!["G", "A"], alleles.iter().flatten().collect::<Vec<_>>());
+}
+
+#[test]
+fn parquet_dosages_in_row_groups() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("dosages.parquet");
+    ArrowExporter::new(ExportFormat::Parquet)
+        .with_sample_columns(SampleColumns::Dosage)
+        .with_row_group_size(25)
+        .export(File::create(&path).unwrap(), create_bgen_and_read())
+        .unwrap();
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(4, builder.metadata().num_row_groups());
+    let bgen_stream = create_bgen_and_read();
+    let samples = bgen_stream.samples.clone();
+    let expected: Vec<_> = bgen_stream
+        .map(|r| r.unwrap().data_block.dosages().unwrap())
+        .collect();
+    assert_eq!(7 + samples.len(), builder.schema().fields().len());
+    let mut variant = 0;
+    for batch in builder.build().unwrap() {
+        let batch = batch.unwrap();
+        for (sample_index, sample) in samples.iter().enumerate() {
+            let column = batch[sample.as_str()].as_primitive::<Float64Type>();
+            for row in 0..batch.num_rows() {
+                let dosage = (!column.is_null(row)).then(|| column.value(row));
+                assert_eq!(expected[variant + row][sample_index], dosage);
+            }
+        }
+        variant += batch.num_rows();
+    }
+    assert_eq!(100, variant);
+}
+
+#[test]
+fn ipc_probabilities() {
+    let mut ipc = Vec::new();
+    ArrowExporter::new(ExportFormat::Ipc)
+        .with_sample_columns(SampleColumns::Probabilities)
+        .export(&mut ipc, create_bgen_and_read())
+        .unwrap();
+    let batches: Vec<_> = FileReader::try_new(Cursor::new(ipc), None)
+        .unwrap()
+        .map(|b| b.unwrap())
+        .collect();
+    assert_eq!(1, batches.len());
+    let bgen_stream = create_bgen_and_read();
+    let first_sample = bgen_stream.samples[0].clone();
+    let first_variant = bgen_stream.into_iter().next().unwrap().unwrap();
+    let expected = &first_variant.data_block.sample_probabilities()[0];
+    let probabilities = batches[0][first_sample.as_str()].as_list::<i32>().value(0);
+    let probabilities: Vec<_> = probabilities
+        .as_primitive::<Float64Type>()
+        .values()
+        .to_vec();
+    assert_eq!(expected.probabilities, probabilities);
+}
+
+#[test]
+fn empty_row_group_size_is_an_error() {
+    let result = ArrowExporter::new(ExportFormat::Parquet)
+        .with_row_group_size(0)
+        .export(Vec::new(), create_bgen_and_read());
+    assert!(result.is_err());
+}
+
+#[test]
+fn sample_columns_without_identifiers() {
+    let bgen_bytes = write_samples(None);
+    let mut ipc = Vec::new();
+    ArrowExporter::new(ExportFormat::Ipc)
+        .with_sample_columns(SampleColumns::Dosage)
+        .export(&mut ipc, read_bytes(bgen_bytes, true))
+        .unwrap();
+    let reader = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+    let schema = reader.schema();
+    let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(["sample_0", "sample_1"], names[7..]);
+}
+
+#[test]
+fn ambiguous_sample_names_are_errors() {
+    for samples in [["chr", "sample"], ["sample", "sample"]] {
+        let samples = samples.map(str::to_string).to_vec();
+        let result = ArrowExporter::new(ExportFormat::Ipc)
+            .with_sample_columns(SampleColumns::Probabilities)
+            .export(Vec::new(), read_bytes(write_samples(Some(samples)), true));
+        assert!(result.is_err());
+    }
+    // without sample columns the names do not matter
+    let samples = vec!["chr".to_string(), "chr".to_string()];
+    assert!(ArrowExporter::new(ExportFormat::Ipc)
+        .export(Vec::new(), read_bytes(write_samples(Some(samples)), true))
+        .is_ok());
+}
+
+#[test]
+fn dosages_of_multiallelic_variants_are_null() {
+    let mut writer = BgenWriter::builder(2)
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let biallelic = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant(
+            "",
+            "rs1",
+            "1",
+            10,
+            &biallelic,
+            &[0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        )
+        .unwrap();
+    let triallelic = ["A".to_string(), "G".to_string(), "T".to_string()];
+    let probabilities = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+    writer
+        .write_variant("", "rs2", "1", 20, &triallelic, &probabilities)
+        .unwrap();
+    let bgen_bytes = writer.into_inner().unwrap().into_inner();
+    let mut ipc = Vec::new();
+    let variant_num = ArrowExporter::new(ExportFormat::Ipc)
+        .with_sample_columns(SampleColumns::Dosage)
+        .export(&mut ipc, read_bytes(bgen_bytes, true))
+        .unwrap();
+    assert_eq!(2, variant_num);
+    let batches: Vec<_> = FileReader::try_new(Cursor::new(ipc), None)
+        .unwrap()
+        .map(|b| b.unwrap())
+        .collect();
+    for sample in ["sample_0", "sample_1"] {
+        let column = batches[0][sample].as_primitive::<Float64Type>();
+        assert!(!column.is_null(0));
+        assert!(column.is_null(1));
+    }
+    let first = batches[0]["sample_1"].as_primitive::<Float64Type>();
+    assert!((first.value(0) - 2.0).abs() < 1e-4);
+}
+
+/// Bgen file of one variant and two samples
+fn write_samples(samples: Option<Vec<String>>) -> Vec<u8> {
+    let mut builder = BgenWriter::builder(2);
+    if let Some(samples) = samples {
+        builder = builder.with_samples(samples);
+    }
+    let mut writer = builder.build(Cursor::new(Vec::new())).unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant(
+            "",
+            "rs1",
+            "1",
+            10,
+            &alleles,
+            &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        )
+        .unwrap();
+    writer.into_inner().unwrap().into_inner()
+}
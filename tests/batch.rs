@@ -0,0 +1,167 @@
+#![cfg(feature = "ndarray")]
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::batch::{BatchReader, BatchValues};
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::writer::BgenWriter;
+use bgen_reader::parser::FilterArgs;
+use common::create_bgen_and_read;
+use std::io::Cursor;
+
+#[test]
+fn dosage_batches() {
+    let expected: Vec<_> = create_bgen_and_read()
+        .map(|r| r.unwrap().data_block.dosages().unwrap())
+        .collect();
+    let batches: Vec<_> = create_bgen_and_read()
+        .batches(30)
+        .unwrap()
+        .map(|b| b.unwrap())
+        .collect();
+    let shapes: Vec<_> = batches.iter().map(|b| b.values.shape().to_vec()).collect();
+    assert_eq!(
+        vec![vec![30, 100], vec![30, 100], vec![30, 100], vec![10, 100]],
+        shapes
+    );
+    let mut variant = 0;
+    for batch in &batches {
+        assert_eq!(batch.values.nrows(), batch.variants.len());
+        for (row, dosages) in batch.values.rows().into_iter().enumerate() {
+            for (sample, &dosage) in dosages.iter().enumerate() {
+                match expected[variant + row][sample] {
+                    Some(expected) => assert_eq!(expected as f32, dosage),
+                    None => assert!(dosage.is_nan()),
+                }
+            }
+        }
+        variant += batch.variants.len();
+    }
+}
+
+#[test]
+fn probability_batch_of_selected_samples() {
+    let bgen_stream = create_bgen_and_read();
+    let samples = vec![
+        bgen_stream.samples[7].clone(),
+        bgen_stream.samples[2].clone(),
+    ];
+    let first_variant = create_bgen_and_read().next().unwrap().unwrap();
+    let expected = first_variant.data_block.sample_probabilities();
+    let batch = BatchReader::new(bgen_stream, 5)
+        .unwrap()
+        .with_values(BatchValues::Probabilities)
+        .with_samples(&samples)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(&[5, 6], batch.values.shape());
+    let first_row: Vec<_> = batch.values.row(0).to_vec();
+    let expected_row: Vec<_> = expected[7]
+        .probabilities
+        .iter()
+        .chain(expected[2].probabilities.iter())
+        .map(|&p| p as f32)
+        .collect();
+    assert_eq!(expected_row, first_row);
+}
+
+#[test]
+fn window_of_variants() {
+    let mut bgen_stream = create_bgen_and_read();
+    bgen_stream
+        .collect_filters(FilterArgs::default().with_range_incl_str("1:0-952567".to_string()))
+        .unwrap();
+    let expected = create_bgen_and_read()
+        .filter(|r| r.as_ref().unwrap().pos <= 952567)
+        .count();
+    let batch = bgen_stream.batches(1000).unwrap().next().unwrap().unwrap();
+    assert!(expected > 0 && expected < 100);
+    assert_eq!(expected, batch.values.nrows());
+}
+
+#[test]
+fn unknown_samples_are_errors() {
+    assert!(create_bgen_and_read()
+        .batches(10)
+        .unwrap()
+        .with_samples(&["not_a_sample".to_string()])
+        .is_err());
+    assert!(create_bgen_and_read()
+        .batches(10)
+        .unwrap()
+        .with_sample_indices(vec![100])
+        .is_err());
+}
+
+#[test]
+fn empty_batch_size_is_an_error() {
+    assert!(create_bgen_and_read().batches(0).is_err());
+}
+
+#[test]
+fn variant_with_all_samples_missing() {
+    let mut writer = BgenWriter::builder(2)
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant("", "rs1", "1", 10, &alleles, &[f64::NAN; 6])
+        .unwrap();
+    writer
+        .write_variant(
+            "",
+            "rs2",
+            "1",
+            20,
+            &alleles,
+            &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        )
+        .unwrap();
+    let bgen_bytes = writer.into_inner().unwrap().into_inner();
+    let mut bgen_stream = BgenStream::from_bytes(bgen_bytes, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let batch = bgen_stream
+        .batches(2)
+        .unwrap()
+        .with_values(BatchValues::Probabilities)
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(&[2, 6], batch.values.shape());
+    assert!(batch.values.row(0).iter().all(|p| p.is_nan()));
+    assert_eq!(
+        vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        batch.values.row(1).to_vec()
+    );
+}
+
+#[test]
+fn dosages_of_multiallelic_variants_are_nan() {
+    let mut writer = BgenWriter::builder(2)
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let triallelic = ["A".to_string(), "G".to_string(), "T".to_string()];
+    let probabilities = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+    writer
+        .write_variant("", "rs1", "1", 10, &triallelic, &probabilities)
+        .unwrap();
+    let biallelic = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant(
+            "",
+            "rs2",
+            "1",
+            20,
+            &biallelic,
+            &[0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        )
+        .unwrap();
+    let bgen_bytes = writer.into_inner().unwrap().into_inner();
+    let mut bgen_stream = BgenStream::from_bytes(bgen_bytes, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let batch = bgen_stream.batches(2).unwrap().next().unwrap().unwrap();
+    assert_eq!(&[2, 2], batch.values.shape());
+    assert!(batch.values.row(0).iter().all(|d| d.is_nan()));
+    assert_eq!(vec![1.0, 2.0], batch.values.row(1).to_vec());
+}
@@ -0,0 +1,181 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::writer::BgenWriter;
+use common::{create_bgen_and_read, read_bytes, read_path};
+use std::io::Cursor;
+use tempfile::tempdir;
+
+#[test]
+fn copy_variants_with_writer() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("copy.bgen");
+    let output = output.to_str().unwrap();
+    let bgen_stream = create_bgen_and_read();
+    let samples = bgen_stream.samples.clone();
+    let original: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    let mut writer = BgenWriter::builder(samples.len() as u32)
+        .with_samples(samples.clone())
+        .create(output)
+        .unwrap();
+    for variant in &original {
+        let probabilities: Vec<_> = variant
+            .data_block
+            .sample_probabilities()
+            .into_iter()
+            .flat_map(|sample| sample.probabilities)
+            .collect();
+        writer
+            .write_variant(
+                &variant.variants_id,
+                &variant.rsid,
+                &variant.chr,
+                variant.pos,
+                &variant.alleles,
+                &probabilities,
+            )
+            .unwrap();
+    }
+    assert_eq!(100, writer.finish().unwrap());
+    let (header, copied_samples, variants) = read_path(output);
+    assert_eq!(100, header.variant_num);
+    assert_eq!(samples, copied_samples);
+    assert_eq!(original, variants);
+}
+
+#[test]
+fn write_missing_and_phased_samples() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("phased.bgen");
+    let output = output.to_str().unwrap();
+    let mut writer = BgenWriter::builder(2)
+        .with_phased(true)
+        .with_compression(false)
+        .with_bits(8)
+        .create(output)
+        .unwrap();
+    let alleles = ["A".to_string(), "C".to_string(), "T".to_string()];
+    let probabilities = [
+        1.0,
+        0.0,
+        0.0,
+        0.2,
+        0.3,
+        0.5,
+        f64::NAN,
+        f64::NAN,
+        f64::NAN,
+        f64::NAN,
+        f64::NAN,
+        f64::NAN,
+    ];
+    writer
+        .write_variant("var_1", "rs1", "2", 500, &alleles, &probabilities)
+        .unwrap();
+    assert_eq!(1, writer.finish().unwrap());
+    let (header, samples, variants) = read_path(output);
+    assert!(!header.header_flags.sample_id_present);
+    assert!(samples.is_empty());
+    let data_block = &variants[0].data_block;
+    assert!(data_block.phased);
+    assert_eq!(3, data_block.number_alleles);
+    let sample_probabilities = data_block.sample_probabilities();
+    assert!(!sample_probabilities[0].missing);
+    assert!(sample_probabilities[1].missing);
+    let first = &sample_probabilities[0].probabilities;
+    assert_eq!(6, first.len());
+    for (p, q) in first.iter().zip(probabilities.iter()) {
+        assert!((p - q).abs() <= 1.0 / 255.0);
+    }
+}
+
+#[test]
+fn layout_1_writer() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("layout_1.bgen");
+    let output = output.to_str().unwrap();
+    let mut writer = BgenWriter::builder(1)
+        .with_layout(1)
+        .create(output)
+        .unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant("var_1", "rs1", "1", 10, &alleles, &[0.25, 0.5, 0.25])
+        .unwrap();
+    writer.finish().unwrap();
+    let (header, _, variants) = read_path(output);
+    assert_eq!(1, header.header_flags.layout_id);
+    let dosage = variants[0].data_block.dosages().unwrap()[0].unwrap();
+    assert!((dosage - 1.0).abs() < 1e-4);
+
+    assert!(BgenWriter::builder(1)
+        .with_layout(1)
+        .with_bits(8)
+        .create(output)
+        .is_err());
+}
+
+#[test]
+fn layout_1_rejects_what_it_cannot_hold() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("layout_1.bgen");
+    let output = output.to_str().unwrap();
+    assert!(BgenWriter::builder(1)
+        .with_layout(1)
+        .with_phased(true)
+        .create(output)
+        .is_err());
+    assert!(BgenWriter::builder(1)
+        .with_layout(1)
+        .with_ploidy(1)
+        .build(Cursor::new(Vec::new()))
+        .is_err());
+    // nothing is created when the settings are wrong
+    assert!(!std::path::Path::new(output).exists());
+
+    let mut writer = BgenWriter::builder(1)
+        .with_layout(1)
+        .create(output)
+        .unwrap();
+    let alleles = ["A".to_string(), "C".to_string(), "T".to_string()];
+    let probabilities = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    assert!(writer
+        .write_variant("var_1", "rs1", "1", 10, &alleles, &probabilities)
+        .is_err());
+    assert_eq!(0, writer.finish().unwrap());
+}
+
+#[test]
+fn wrong_number_of_probabilities_fails() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("wrong.bgen");
+    let output = output.to_str().unwrap();
+    let mut writer = BgenWriter::builder(2).create(output).unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    assert!(writer
+        .write_variant("var_1", "rs1", "1", 10, &alleles, &[0.25, 0.5, 0.25])
+        .is_err());
+    assert!(BgenWriter::builder(2)
+        .with_samples(vec!["only_one".to_string()])
+        .create(output)
+        .is_err());
+}
+
+#[test]
+fn write_after_other_data() {
+    let prefix = b"not a bgen file".to_vec();
+    let mut cursor = Cursor::new(prefix.clone());
+    cursor.set_position(prefix.len() as u64);
+    let mut writer = BgenWriter::builder(1).build(cursor).unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant("var_1", "rs1", "1", 10, &alleles, &[1.0, 0.0, 0.0])
+        .unwrap();
+    let cursor = writer.into_inner().unwrap();
+    assert_eq!(cursor.get_ref().len() as u64, cursor.position());
+    let bytes = cursor.into_inner();
+    assert_eq!(prefix[..], bytes[..prefix.len()]);
+    let bgen_stream = read_bytes(bytes[prefix.len()..].to_vec(), true);
+    assert_eq!(1, bgen_stream.header.variant_num);
+    let variants: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    assert_eq!("rs1", variants[0].rsid);
+}
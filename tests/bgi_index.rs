@@ -0,0 +1,47 @@
+extern crate bgen_reader;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::bgi_writer::write_index;
+use sqlite::State;
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn index_has_a_row_per_variant() {
+    let dir = tempdir().unwrap();
+    let bgen_path = dir.path().join("indexed.bgen");
+    std::fs::copy(BGEN_PATH, &bgen_path).unwrap();
+    let mut bgen_stream = BgenStream::from_path(bgen_path.to_str().unwrap(), false, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let index_path = write_index(bgen_stream).unwrap();
+
+    let connection = sqlite::open(index_path).unwrap();
+    let mut statement = connection.prepare("SELECT COUNT(*) FROM Variant").unwrap();
+    assert_eq!(State::Row, statement.next().unwrap());
+    assert_eq!(100, statement.read::<i64, _>(0).unwrap());
+    let mut statement = connection.prepare("SELECT COUNT(*) FROM Metadata").unwrap();
+    assert_eq!(State::Row, statement.next().unwrap());
+    assert_eq!(1, statement.read::<i64, _>(0).unwrap());
+
+    let mut statement = connection
+        .prepare(
+            "SELECT file_start_position, size_in_bytes FROM Variant ORDER BY file_start_position",
+        )
+        .unwrap();
+    let mut offsets = Vec::new();
+    while statement.next().unwrap() == State::Row {
+        offsets.push((
+            statement.read::<i64, _>(0).unwrap() as usize,
+            statement.read::<i64, _>(1).unwrap() as usize,
+        ));
+    }
+    let mut bgen_stream = BgenStream::from_path(BGEN_PATH, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let expected: Vec<_> = bgen_stream
+        .map(|r| {
+            let variant_data = r.unwrap();
+            (variant_data.file_start_position, variant_data.size_in_bytes)
+        })
+        .collect();
+    assert_eq!(expected, offsets);
+}
@@ -0,0 +1,130 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::bgi_writer::write_index;
+use bgen_reader::bgen::chunks::{open_chunk, plan_chunks, read_locations, ChunkSize};
+use bgen_reader::parser::FilterArgs;
+use common::{create_bgen_and_read, open_path};
+use std::path::Path;
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn chunks_of_variants_cover_the_file() {
+    let locations = read_locations(BGEN_PATH).unwrap();
+    let chunks = plan_chunks(&locations, ChunkSize::Variants(30), false);
+    assert_eq!(
+        vec![30, 30, 30, 10],
+        chunks.iter().map(|c| c.num_variants).collect::<Vec<_>>()
+    );
+    let mut bgen_stream = create_bgen_and_read();
+    assert_eq!(
+        bgen_stream.header.start_data_offset as u64 + 4,
+        chunks[0].start_offset
+    );
+    for (chunk, next_chunk) in chunks.iter().zip(chunks.iter().skip(1)) {
+        assert_eq!(chunk.end_offset, next_chunk.start_offset);
+    }
+    let file_size = std::fs::metadata(BGEN_PATH).unwrap().len();
+    assert_eq!(file_size, chunks[3].end_offset);
+    let first = bgen_stream.next().unwrap().unwrap();
+    assert_eq!(
+        (first.chr, first.pos),
+        ("1".to_string(), chunks[0].first_pos)
+    );
+    assert!(chunks.iter().all(|c| c.region().is_some()));
+}
+
+#[test]
+fn chunks_of_bytes_never_split_variants() {
+    let locations = read_locations(BGEN_PATH).unwrap();
+    let chunks = plan_chunks(&locations, ChunkSize::Bytes(1000), false);
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|c| c.end_offset - c.start_offset <= 1000));
+    let num_variants: u32 = chunks.iter().map(|c| c.num_variants).sum();
+    assert_eq!(100, num_variants);
+    let variant_starts: Vec<_> = locations.iter().map(|l| l.file_start_position).collect();
+    assert!(chunks
+        .iter()
+        .all(|c| variant_starts.contains(&c.start_offset)));
+}
+
+#[test]
+fn locations_from_index() {
+    let dir = tempdir().unwrap();
+    let bgen_path = dir.path().join("indexed.bgen");
+    std::fs::copy(BGEN_PATH, &bgen_path).unwrap();
+    let bgen_path = bgen_path.to_str().unwrap();
+    let scanned = read_locations(bgen_path).unwrap();
+    let mut bgen_stream = BgenStream::from_path(bgen_path, false, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let index_path = write_index(bgen_stream).unwrap();
+    assert!(Path::new(&index_path).exists());
+    assert_eq!(scanned, read_locations(bgen_path).unwrap());
+}
+
+#[test]
+fn out_of_date_index_is_ignored() {
+    let dir = tempdir().unwrap();
+    let bgen_path = dir.path().join("rewritten.bgen");
+    std::fs::copy(BGEN_PATH, &bgen_path).unwrap();
+    let bgen_path = bgen_path.to_str().unwrap();
+    let mut bgen_stream = BgenStream::from_path(bgen_path, false, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    write_index(bgen_stream).unwrap();
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream.to_bgen(bgen_path, false).unwrap();
+    let num_variants = open_path(bgen_path).count();
+    assert!(num_variants < 100);
+    assert_eq!(num_variants, read_locations(bgen_path).unwrap().len());
+}
+
+#[test]
+fn index_without_variants_is_ignored() {
+    let dir = tempdir().unwrap();
+    let bgen_path = dir.path().join("empty_index.bgen");
+    std::fs::copy(BGEN_PATH, &bgen_path).unwrap();
+    let bgen_path = bgen_path.to_str().unwrap();
+    let mut bgen_stream = BgenStream::from_path(bgen_path, false, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let index_path = write_index(bgen_stream).unwrap();
+    // as written before the inserts of the index writer were executed
+    sqlite::open(&index_path)
+        .unwrap()
+        .execute("DELETE FROM Variant")
+        .unwrap();
+    assert_eq!(100, read_locations(bgen_path).unwrap().len());
+}
+
+#[test]
+fn stream_restricted_to_chunk() {
+    let locations = read_locations(BGEN_PATH).unwrap();
+    let chunks = plan_chunks(&locations, ChunkSize::Variants(30), false);
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    let chunk_variants: Vec<_> = open_chunk(BGEN_PATH, &chunks[1], true)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(original[30..60], chunk_variants[..]);
+
+    // the copy of the stream made when writing a bgen is restricted too
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("chunk.bgen");
+    let output = output.to_str().unwrap();
+    open_chunk(BGEN_PATH, &chunks[3], false)
+        .unwrap()
+        .to_bgen(output, false)
+        .unwrap();
+    let mut bgen_stream = BgenStream::from_path(output, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    assert_eq!(10, bgen_stream.header.variant_num);
+    let written: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    assert_eq!(10, written.len());
+    for (variant, copy) in original[90..].iter().zip(written.iter()) {
+        assert_eq!(variant.rsid, copy.rsid);
+        assert_eq!(variant.data_block, copy.data_block);
+    }
+}
@@ -0,0 +1,54 @@
+//! Helpers shared by the integration tests, each test file only uses some of them
+#![allow(dead_code)]
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::header::Header;
+use bgen_reader::bgen::variant_data::VariantData;
+use std::fs::File;
+use std::io::Cursor;
+
+pub fn create_bgen_and_read() -> BgenStream<Cursor<Vec<u8>>> {
+    read_test_file(true)
+}
+
+/// Stream of the test file whose header has been read
+pub fn read_test_file(read_data_block: bool) -> BgenStream<Cursor<Vec<u8>>> {
+    let bgen_bytes = include_bytes!("../../data_test/samp_100_var_100.bgen");
+    read_bytes(bgen_bytes.to_vec(), read_data_block)
+}
+
+/// Stream of the bgen file in `bgen_bytes` whose header has been read
+pub fn read_bytes(bgen_bytes: Vec<u8>, read_data_block: bool) -> BgenStream<Cursor<Vec<u8>>> {
+    let mut bgen_stream = BgenStream::from_bytes(bgen_bytes, read_data_block).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    bgen_stream
+}
+
+/// Stream of the file at `path` whose header has been read
+pub fn open_path(path: &str) -> BgenStream<File> {
+    let mut bgen_stream = BgenStream::from_path(path, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    bgen_stream
+}
+
+pub fn read_variants(path: &str) -> Vec<VariantData> {
+    open_path(path).map(|r| r.unwrap()).collect()
+}
+
+/// Header, samples and variants of the file at `path`
+pub fn read_path(path: &str) -> (Header, Vec<String>, Vec<VariantData>) {
+    let bgen_stream = open_path(path);
+    let header = bgen_stream.header.clone();
+    let samples = bgen_stream.samples.clone();
+    let variants: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    (header, samples, variants)
+}
+
+/// The variants are the first `num_variants` of the test file, with the same data blocks
+pub fn assert_same_variants(variants: &[VariantData], num_variants: usize) {
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    assert_eq!(num_variants, variants.len());
+    for (variant, copy) in original.iter().zip(variants.iter()) {
+        assert_eq!(variant.rsid, copy.rsid);
+        assert_eq!(variant.data_block, copy.data_block);
+    }
+}
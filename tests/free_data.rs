@@ -0,0 +1,54 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::reheader::set_free_data;
+use bgen_reader::parser::FilterArgs;
+use common::{assert_same_variants, create_bgen_and_read, read_path};
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+const PROVENANCE: &[u8] = b"imputed with reference panel v2";
+
+#[test]
+fn setting_and_clearing_free_data() {
+    let dir = tempdir().unwrap();
+    let with_free_data = dir.path().join("with_free_data.bgen");
+    let with_free_data = with_free_data.to_str().unwrap();
+    set_free_data(BGEN_PATH, with_free_data, PROVENANCE.to_vec()).unwrap();
+    let (header, samples, variants) = read_path(with_free_data);
+    assert_eq!(PROVENANCE, &header.free_data[..]);
+    assert_eq!(20 + PROVENANCE.len() as u32, header.header_size);
+    assert_eq!(create_bgen_and_read().samples, samples);
+    assert_same_variants(&variants, 100);
+
+    let cleared = dir.path().join("cleared.bgen");
+    let cleared = cleared.to_str().unwrap();
+    set_free_data(with_free_data, cleared, Vec::new()).unwrap();
+    let (header, _, variants) = read_path(cleared);
+    assert!(header.free_data.is_empty());
+    assert_eq!(20, header.header_size);
+    assert_eq!(
+        std::fs::read(BGEN_PATH).unwrap(),
+        std::fs::read(cleared).unwrap()
+    );
+    assert_same_variants(&variants, 100);
+}
+
+#[test]
+fn free_data_kept_on_bgen_write() {
+    let dir = tempdir().unwrap();
+    let with_free_data = dir.path().join("with_free_data.bgen");
+    let with_free_data = with_free_data.to_str().unwrap();
+    set_free_data(BGEN_PATH, with_free_data, PROVENANCE.to_vec()).unwrap();
+    let mut bgen_stream = BgenStream::from_path(with_free_data, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    let output = dir.path().join("filtered.bgen");
+    let output = output.to_str().unwrap();
+    bgen_stream.to_bgen(output, false).unwrap();
+    let (header, _, variants) = read_path(output);
+    assert_eq!(PROVENANCE, &header.free_data[..]);
+    assert_eq!(header.variant_num as usize, variants.len());
+    assert_same_variants(&variants, variants.len());
+}
@@ -0,0 +1,97 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::writer::BgenWriter;
+use bgen_reader::vcf_writer::{write_vcf, write_vcf_to};
+use common::create_bgen_and_read;
+use std::io::Cursor;
+use std::process::Command;
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn write_bgen_to_memory() {
+    let mut bgen_stream = BgenStream::from_path(BGEN_PATH, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let mut buffer = Vec::new();
+    bgen_stream.write_bgen(&mut buffer, false).unwrap();
+
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("copy.bgen");
+    let output = output.to_str().unwrap();
+    let mut bgen_stream = BgenStream::from_path(BGEN_PATH, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    bgen_stream.to_bgen(output, false).unwrap();
+    assert_eq!(std::fs::read(output).unwrap(), buffer);
+
+    let mut copy = BgenStream::from_bytes(buffer, true).unwrap();
+    copy.read_offset_and_header().unwrap();
+    assert_eq!(create_bgen_and_read().samples, copy.samples);
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    let copied: Vec<_> = copy.map(|r| r.unwrap()).collect();
+    assert_eq!(original, copied);
+}
+
+#[test]
+fn write_vcf_to_memory() {
+    let mut buffer = Vec::new();
+    write_vcf_to(&mut buffer, create_bgen_and_read()).unwrap();
+
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("copy.vcf");
+    let output = output.to_str().unwrap();
+    let mut bgen_stream = BgenStream::from_path(BGEN_PATH, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    write_vcf(output, bgen_stream).unwrap();
+    assert_eq!(std::fs::read(output).unwrap(), buffer);
+    assert!(buffer.starts_with(b"##fileformat=VCFv4.2\n"));
+}
+
+#[test]
+fn bgen_writer_to_memory() {
+    let mut writer = BgenWriter::builder(1)
+        .with_samples(vec!["sample".to_string()])
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let alleles = ["A".to_string(), "T".to_string()];
+    for pos in [10, 20] {
+        writer
+            .write_variant("", "rs", "1", pos, &alleles, &[0.0, 0.0, 1.0])
+            .unwrap();
+    }
+    let buffer = writer.into_inner().unwrap().into_inner();
+    let mut bgen_stream = BgenStream::from_bytes(buffer, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    assert_eq!(2, bgen_stream.header.variant_num);
+    assert_eq!(vec!["sample".to_string()], bgen_stream.samples);
+    let positions: Vec<_> = bgen_stream.map(|r| r.unwrap().pos).collect();
+    assert_eq!(vec![10, 20], positions);
+}
+
+#[test]
+fn output_of_10_bits_file_is_clean() {
+    let mut buffer = Vec::new();
+    create_bgen_and_read()
+        .with_output_bits(Some(10))
+        .write_bgen(&mut buffer, false)
+        .unwrap();
+    let dir = tempdir().unwrap();
+    let bits_10 = dir.path().join("bits_10.bgen");
+    std::fs::write(&bits_10, &buffer).unwrap();
+
+    let mut bgen_stream = BgenStream::from_bytes(buffer.clone(), true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let mut vcf = Vec::new();
+    write_vcf_to(&mut vcf, bgen_stream).unwrap();
+    let mut expected = Vec::new();
+    write_vcf_to(&mut expected, create_bgen_and_read()).unwrap();
+    assert_eq!(expected, vcf);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bgen_reader"))
+        .args(["-f", bits_10.to_str().unwrap(), "vcf", "-"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(expected, output.stdout);
+}
@@ -0,0 +1,73 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::info::{BgenInfo, ChromosomeInfo, InfoFormat};
+use bgen_reader::bgen::writer::BgenWriter;
+use common::create_bgen_and_read;
+use serde_json::Value;
+use std::io::Cursor;
+
+#[test]
+fn info_of_test_file() {
+    let bgen_info = BgenInfo::from_stream(create_bgen_and_read(), false).unwrap();
+    assert_eq!(100, bgen_info.variant_num);
+    assert_eq!(100, bgen_info.sample_num);
+    assert_eq!(2, bgen_info.layout_id);
+    assert!(bgen_info.compressed);
+    assert!(bgen_info.sample_ids_present);
+    assert_eq!(None, bgen_info.samples);
+    assert_eq!(
+        vec![ChromosomeInfo {
+            chr: "1".to_string(),
+            num_variants: 100,
+            min_pos: 752566,
+            max_pos: 2756397,
+        }],
+        bgen_info.chromosomes
+    );
+    let mut text = Vec::new();
+    bgen_info.write(&mut text, InfoFormat::Text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("Number of variants: 100\n"));
+    assert!(text.contains("1\t100\t752566\t2756397\n"));
+}
+
+#[test]
+fn info_as_json_with_samples() {
+    let bgen_info = BgenInfo::from_stream(create_bgen_and_read(), true).unwrap();
+    let mut json = Vec::new();
+    bgen_info.write(&mut json, InfoFormat::Json).unwrap();
+    let json: Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(1728, json["start_data_offset"]);
+    assert_eq!(100, json["samples"].as_array().unwrap().len());
+    assert_eq!("AFR_ACB-HG01879", json["samples"][0]);
+    assert_eq!("1", json["chromosomes"][0]["chr"]);
+}
+
+#[test]
+fn chromosomes_in_file_order() {
+    let mut writer = BgenWriter::builder(1)
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let alleles = ["A".to_string(), "C".to_string()];
+    for (chr, pos) in [("2", 50), ("2", 10), ("1", 30), ("X", 5)] {
+        writer
+            .write_variant("", "", chr, pos, &alleles, &[1.0, 0.0, 0.0])
+            .unwrap();
+    }
+    let bytes = writer.into_inner().unwrap().into_inner();
+    let mut bgen_stream = BgenStream::from_bytes(bytes, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let bgen_info = BgenInfo::from_stream(bgen_stream, true).unwrap();
+    assert!(!bgen_info.sample_ids_present);
+    assert_eq!(Some(Vec::new()), bgen_info.samples);
+    let summary: Vec<_> = bgen_info
+        .chromosomes
+        .iter()
+        .map(|c| (c.chr.as_str(), c.num_variants, c.min_pos, c.max_pos))
+        .collect();
+    assert_eq!(
+        vec![("2", 2, 10, 50), ("1", 1, 30, 30), ("X", 1, 5, 5)],
+        summary
+    );
+}
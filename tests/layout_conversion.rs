@@ -0,0 +1,88 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::variant_data::VariantData;
+use common::{create_bgen_and_read, read_path};
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn downgrade_to_layout_1() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("layout_1.bgen");
+    let output = output.to_str().unwrap();
+    convert(BGEN_PATH, output, Some(1), None);
+    let (header, samples, variants) = read_path(output);
+    assert_eq!(1, header.header_flags.layout_id);
+    assert_eq!(create_bgen_and_read().samples, samples);
+    assert_eq!(100, variants.len());
+    for variant in &variants {
+        assert_eq!(Some(100), variant.number_individuals);
+        assert_eq!(2, variant.number_alleles);
+        assert_eq!(16, variant.data_block.bytes_probability);
+    }
+    assert_close_dosages(&variants, 1.0 / 32768.0 + 1.0 / 65535.0);
+}
+
+#[test]
+fn upgrade_from_layout_1() {
+    let dir = tempdir().unwrap();
+    let layout_1 = dir.path().join("layout_1.bgen");
+    let layout_1 = layout_1.to_str().unwrap();
+    convert(BGEN_PATH, layout_1, Some(1), None);
+    let layout_2 = dir.path().join("layout_2.bgen");
+    let layout_2 = layout_2.to_str().unwrap();
+    convert(layout_1, layout_2, Some(2), Some(8));
+    let (header, _, variants) = read_path(layout_2);
+    assert_eq!(2, header.header_flags.layout_id);
+    assert_eq!(100, variants.len());
+    for variant in &variants {
+        assert_eq!(None, variant.number_individuals);
+        assert_eq!(8, variant.data_block.bytes_probability);
+    }
+    assert_close_dosages(&variants, 2.0 / 255.0);
+}
+
+#[test]
+fn bits_with_layout_1_fail() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("layout_1.bgen");
+    let mut bgen_stream = BgenStream::from_path(BGEN_PATH, false, true)
+        .unwrap()
+        .with_output_layout(Some(1))
+        .with_output_bits(Some(8));
+    bgen_stream.read_offset_and_header().unwrap();
+    assert!(bgen_stream
+        .to_bgen(output.to_str().unwrap(), false)
+        .is_err());
+}
+
+fn convert(input: &str, output: &str, layout: Option<u8>, bits: Option<u8>) {
+    let mut bgen_stream = BgenStream::from_path(input, false, true)
+        .unwrap()
+        .with_output_layout(layout)
+        .with_output_bits(bits);
+    bgen_stream.read_offset_and_header().unwrap();
+    bgen_stream.to_bgen(output, false).unwrap();
+}
+
+fn assert_close_dosages(variants: &[VariantData], tolerance: f64) {
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    for (variant, copy) in original.iter().zip(variants.iter()) {
+        assert_eq!(variant.rsid, copy.rsid);
+        assert_eq!(variant.alleles, copy.alleles);
+        for (dosage, copied) in variant
+            .data_block
+            .dosages()
+            .unwrap()
+            .iter()
+            .zip(copy.data_block.dosages().unwrap().iter())
+        {
+            match (dosage, copied) {
+                (Some(dosage), Some(copied)) => assert!((dosage - copied).abs() <= tolerance),
+                _ => assert_eq!(dosage, copied),
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::list_writer::{write_list, ListField};
+use bgen_reader::parser::{ListFieldsArgs, VariantOutput};
+use common::read_test_file;
+use serde_json::Value;
+
+#[test]
+fn json_lines_output() {
+    let output = list(&VariantOutput::Json(ListFieldsArgs {
+        fields: ListField::DEFAULT_FIELDS.to_vec(),
+    }));
+    let records: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(100, records.len());
+    let first = &records[0];
+    assert_eq!("1", first["chr"]);
+    assert_eq!(752566, first["pos"]);
+    assert_eq!("1_752566_G_A", first["rsid"]);
+    assert_eq!("", first["alternate_id"]);
+    assert_eq!(serde_json::json!(["G", "A"]), first["alleles"]);
+    assert!(first.get("file_offset").is_none());
+}
+
+#[test]
+fn json_keys_follow_selected_fields() {
+    let fields = vec![ListField::SizeInBytes, ListField::Rsid, ListField::Chr];
+    let output = list(&VariantOutput::Json(ListFieldsArgs {
+        fields: fields.clone(),
+    }));
+    let first: Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+    let keys: Vec<_> = first.as_object().unwrap().keys().cloned().collect();
+    let names: Vec<_> = fields.iter().map(|field| field.name()).collect();
+    assert_eq!(names, keys);
+}
+
+#[test]
+fn tsv_output_with_selected_fields() {
+    let fields = vec![
+        ListField::Pos,
+        ListField::Alleles,
+        ListField::FileOffset,
+        ListField::SizeInBytes,
+    ];
+    let output = list(&VariantOutput::Tsv(ListFieldsArgs { fields }));
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(101, lines.len());
+    assert_eq!("pos\talleles\tfile_offset\tsize_in_bytes", lines[0]);
+    assert_eq!("752566\tG,A\t1732\t127", lines[1]);
+    let variants: Vec<_> = read_test_file(false).map(|r| r.unwrap()).collect();
+    for (line, variant) in lines[1..].iter().zip(variants.iter()) {
+        let columns: Vec<_> = line.split('\t').collect();
+        assert_eq!(variant.file_start_position.to_string(), columns[2]);
+        assert_eq!(variant.size_in_bytes.to_string(), columns[3]);
+    }
+}
+
+#[test]
+fn stats_fields() {
+    let variant_output = VariantOutput::Json(ListFieldsArgs {
+        fields: vec![ListField::Rsid, ListField::MinorAlleleFrequency],
+    });
+    assert!(variant_output.needs_data_block());
+    let mut buffer = Vec::new();
+    write_list(&mut buffer, read_test_file(true), &variant_output).unwrap();
+    let first: Value =
+        serde_json::from_str(String::from_utf8(buffer).unwrap().lines().next().unwrap()).unwrap();
+    let maf = first["minor_allele_frequency"].as_f64().unwrap();
+    assert!((0.0..=0.5).contains(&maf));
+    assert!(!VariantOutput::Bgenix.needs_data_block());
+}
+
+#[test]
+fn tsv_stats_match_json() {
+    let fields = vec![
+        ListField::Rsid,
+        ListField::MinorAlleleFrequency,
+        ListField::ImputeInfo,
+    ];
+    let mut tsv = Vec::new();
+    write_list(
+        &mut tsv,
+        read_test_file(true),
+        &VariantOutput::Tsv(ListFieldsArgs {
+            fields: fields.clone(),
+        }),
+    )
+    .unwrap();
+    let mut json = Vec::new();
+    write_list(
+        &mut json,
+        read_test_file(true),
+        &VariantOutput::Json(ListFieldsArgs { fields }),
+    )
+    .unwrap();
+    let tsv = String::from_utf8(tsv).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    for (tsv_line, json_line) in tsv.lines().skip(1).zip(json.lines()) {
+        let record: Value = serde_json::from_str(json_line).unwrap();
+        let columns: Vec<_> = tsv_line.split('\t').collect();
+        assert_eq!(record["rsid"], columns[0]);
+        for (key, column) in [
+            ("minor_allele_frequency", columns[1]),
+            ("impute_info", columns[2]),
+        ] {
+            let value = record[key].as_f64().unwrap();
+            assert!((value - column.parse::<f64>().unwrap()).abs() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn bgenix_output_has_chromosome() {
+    let output = list(&VariantOutput::Bgenix);
+    let lines: Vec<_> = output.lines().collect();
+    let header: Vec<_> = lines[0].split('\t').collect();
+    let first: Vec<_> = lines[1].split('\t').collect();
+    assert_eq!("chromosome", header[2]);
+    assert_eq!("1", first[2]);
+    assert_eq!("752566", first[3]);
+    assert_eq!("A", first[6]);
+}
+
+fn list(variant_output: &VariantOutput) -> String {
+    let mut buffer = Vec::new();
+    write_list(&mut buffer, read_test_file(false), variant_output).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
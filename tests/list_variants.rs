@@ -1,7 +1,9 @@
 extern crate bgen_reader;
 use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::range_set::RangeSet;
 use bgen_reader::bgen::variant_data::{DataBlock, VariantData};
-use bgen_reader::parser::FilterArgs;
+use bgen_reader::bgen::writer::BgenWriter;
+use bgen_reader::parser::{AlleleFilters, FilterArgs, Range, StatFilters};
 use std::io::Cursor;
 use std::io::Write;
 use tempfile::tempdir;
@@ -210,6 +212,241 @@ fn test_double_filter_file() {
     );
 }
 
+#[test]
+fn test_filter_stats() {
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_stat_filters(StatFilters {
+        min_maf: Some(0.2),
+        max_maf: Some(0.3),
+        ..Default::default()
+    });
+    bgen_stream.collect_filters(list_args).unwrap();
+    let variant_data: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    assert_eq!(21, variant_data.len());
+}
+
+#[test]
+fn test_filter_stats_and_range() {
+    let mut bgen_stream = BgenStream::from_bytes(
+        include_bytes!("../data_test/samp_100_var_100.bgen").to_vec(),
+        false,
+    )
+    .unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let list_args = FilterArgs::default()
+        .with_range_incl_str("1:0-952567".to_string())
+        .with_stat_filters(StatFilters {
+            min_maf: Some(0.2),
+            hwe_p: Some(1e-6),
+            ..Default::default()
+        });
+    bgen_stream.collect_filters(list_args).unwrap();
+    let variant_data: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    assert_eq!(5, variant_data.len());
+}
+
+#[test]
+fn test_filter_alleles() {
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_allele_filters(AlleleFilters {
+        biallelic: true,
+        snvs_only: true,
+        exclude_ambiguous: true,
+        ..Default::default()
+    });
+    bgen_stream.collect_filters(list_args).unwrap();
+    assert_eq!(100, bgen_stream.count());
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_allele_filters(AlleleFilters {
+        indels_only: true,
+        ..Default::default()
+    });
+    bgen_stream.collect_filters(list_args).unwrap();
+    assert_eq!(0, bgen_stream.count());
+}
+
+#[test]
+fn test_allele_filters_accept() {
+    let alleles = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let indels = AlleleFilters {
+        indels_only: true,
+        ..Default::default()
+    };
+    assert!(indels.accept(&alleles(&["A", "AT"])));
+    assert!(!indels.accept(&alleles(&["AC", "GT"])));
+    let max_two = AlleleFilters {
+        max_alleles: Some(2),
+        exclude_ambiguous: true,
+        ..Default::default()
+    };
+    assert!(!max_two.accept(&alleles(&["A", "C", "G"])));
+    assert!(!max_two.accept(&alleles(&["c", "g"])));
+    assert!(max_two.accept(&alleles(&["A", "C"])));
+}
+
+#[test]
+fn test_filter_variant_key() {
+    let count_with = |list_args: FilterArgs| {
+        let mut bgen_stream = create_bgen_and_read();
+        bgen_stream.collect_filters(list_args).unwrap();
+        bgen_stream.count()
+    };
+    let list_args = FilterArgs::default().with_variant_key_incl_str("1:752566:G:A".to_string());
+    assert_eq!(1, count_with(list_args));
+    let list_args = FilterArgs::default().with_variant_key_incl_str("chr1:752566:a:g".to_string());
+    assert_eq!(0, count_with(list_args));
+    let list_args = FilterArgs::default()
+        .with_variant_key_incl_str("chr1:752566:a:g".to_string())
+        .with_unordered_alleles(true);
+    assert_eq!(1, count_with(list_args));
+    let list_args = FilterArgs::default().with_variant_id_incl_str("id_1".to_string());
+    assert_eq!(0, count_with(list_args));
+}
+
+#[test]
+fn test_filter_variant_id_and_contig_key() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("ids.bgen");
+    let path = path.to_str().unwrap();
+    let mut writer = BgenWriter::builder(1).create(path).unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    let variants = [
+        ("id_1", "1", 100),
+        ("id_2", "1", 200),
+        ("id_3", "chrUn_gl000220", 100),
+    ];
+    for (variant_id, chr, pos) in variants {
+        writer
+            .write_variant(variant_id, "rs", chr, pos, &alleles, &[1.0, 0.0, 0.0])
+            .unwrap();
+    }
+    writer.finish().unwrap();
+    let ids_with = |list_args: FilterArgs| {
+        let mut bgen_stream = BgenStream::from_path(path, false, false).unwrap();
+        bgen_stream.read_offset_and_header().unwrap();
+        bgen_stream.collect_filters(list_args).unwrap();
+        bgen_stream
+            .map(|r| r.unwrap().variants_id)
+            .collect::<Vec<_>>()
+    };
+    let list_args = FilterArgs::default().with_variant_id_incl_str("id_1\nid_3".to_string());
+    assert_eq!(vec!["id_1", "id_3"], ids_with(list_args));
+    let list_args = FilterArgs::default().with_variant_id_excl_str("id_2".to_string());
+    assert_eq!(vec!["id_1", "id_3"], ids_with(list_args));
+    let list_args =
+        FilterArgs::default().with_variant_key_incl_str("chrUn_gl000220_100_A_G".to_string());
+    assert_eq!(vec!["id_3"], ids_with(list_args));
+    let list_args =
+        FilterArgs::default().with_variant_key_incl_str("chrUn_gl000220:100:A:G".to_string());
+    assert_eq!(vec!["id_3"], ids_with(list_args));
+}
+
+#[test]
+fn test_filter_variant_key_file() {
+    let mut bgen_stream = create_bgen_and_read();
+    let dir = tempdir().unwrap();
+    let filepath = dir.path().join("tmp_keys");
+    let mut file = std::fs::File::create(filepath.clone()).unwrap();
+    writeln!(file, "1_752566_G_A").unwrap();
+    writeln!(file, "1:752721:A:G").unwrap();
+    writeln!(file).unwrap();
+    let list_args = FilterArgs::default()
+        .with_range_incl_str("1:0-900000".to_string())
+        .with_variant_key_excl_file(filepath.into_os_string().into_string().unwrap());
+    bgen_stream.collect_filters(list_args).unwrap();
+    let variant_data: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    assert_eq!("1_873558_G_T", variant_data[0].rsid);
+}
+
+#[test]
+fn test_filter_assume_sorted() {
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default()
+        .with_range_incl_str("1:0-900000".to_string())
+        .with_range_excl_str("1:800000-850000".to_string())
+        .with_assume_sorted(true);
+    bgen_stream.collect_filters(list_args).unwrap();
+    let variant_data: Vec<_> = bgen_stream.by_ref().map(|r| r.unwrap()).collect();
+    assert_eq!(7, variant_data.len());
+    // the stream stops right after the first variant past the range
+    let bgen_len = include_bytes!("../data_test/samp_100_var_100.bgen").len();
+    assert!(bgen_stream.byte_count < bgen_len / 2);
+}
+
+#[test]
+fn test_range_set() {
+    let range = |chr: &str, start, end| Range {
+        chr: chr.to_string(),
+        start,
+        end,
+        incl: true,
+    };
+    let range_set = RangeSet::new(&[
+        range("1", 100, 200),
+        range("1", 150, 300),
+        range("1", 500, 600),
+        range("2", 0, 10),
+    ]);
+    assert!(range_set.contains("1", 100));
+    assert!(range_set.contains("1", 250));
+    assert!(!range_set.contains("1", 301));
+    assert!(range_set.contains("1", 600));
+    assert!(!range_set.contains("1", 99));
+    assert!(!range_set.contains("3", 5));
+    assert_eq!(Some(600), range_set.last_end("1"));
+}
+
+#[test]
+fn test_filter_region_syntax() {
+    let count_with = |list_args: FilterArgs| {
+        let mut bgen_stream = create_bgen_and_read();
+        bgen_stream.collect_filters(list_args).unwrap();
+        bgen_stream.count()
+    };
+    let list_args = FilterArgs::default().with_range_incl_str("chr1:0-752567".to_string());
+    assert_eq!(1, count_with(list_args));
+    let list_args =
+        FilterArgs::default().with_range_incl_str("01:752566,1:873558-873558".to_string());
+    assert_eq!(2, count_with(list_args));
+    let list_args = FilterArgs::default().with_range_incl_str("1".to_string());
+    assert_eq!(100, count_with(list_args));
+    let list_args = FilterArgs::default().with_range_excl_str("1:-900000".to_string());
+    assert_eq!(93, count_with(list_args));
+}
+
+#[test]
+fn test_filter_bed_file() {
+    let mut bgen_stream = create_bgen_and_read();
+    let dir = tempdir().unwrap();
+    let filepath = dir.path().join("tmp_range.bed");
+    let mut file = std::fs::File::create(filepath.clone()).unwrap();
+    writeln!(file, "track name=test").unwrap();
+    writeln!(file, "chr1\t752565\t752566\tfirst\t0\t+").unwrap();
+    writeln!(file, "chr1\t752721\t752722").unwrap();
+    let list_args = FilterArgs::default()
+        .with_range_incl_file(filepath.into_os_string().into_string().unwrap());
+    bgen_stream.collect_filters(list_args).unwrap();
+    let variant_data: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    // 0-based half-open: only the first variant falls in the BED intervals
+    assert_eq!(1, variant_data.len());
+    assert_eq!("1_752566_G_A", variant_data[0].rsid);
+}
+
+#[test]
+fn test_filter_invalid_range_line() {
+    let mut bgen_stream = create_bgen_and_read();
+    let dir = tempdir().unwrap();
+    let filepath = dir.path().join("tmp_range");
+    let mut file = std::fs::File::create(filepath.clone()).unwrap();
+    writeln!(file, "1:0-10").unwrap();
+    writeln!(file, "1:20-abc").unwrap();
+    let list_args = FilterArgs::default()
+        .with_range_incl_file(filepath.into_os_string().into_string().unwrap());
+    let err = bgen_stream.collect_filters(list_args).unwrap_err();
+    assert!(err.to_string().contains("Line 2"), "{}", err);
+    assert!(err.to_string().contains("1:20-abc"), "{}", err);
+}
+
 fn create_bgen_and_read() -> BgenStream<Cursor<Vec<u8>>> {
     let bgen_bytes = include_bytes!("../data_test/samp_100_var_100.bgen");
     let mut bgen_stream = BgenStream::from_bytes(bgen_bytes.to_vec(), true).unwrap();
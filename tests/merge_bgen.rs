@@ -1,12 +1,15 @@
 extern crate bgen_reader;
 use bgen_reader::bgen::bgen_stream::bgen_merge;
 use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::chromosome::{ChrConvention, ChrRenamer};
+use bgen_reader::bgen::writer::BgenWriter;
 use bgen_reader::parser::FilterArgs;
 use serial_test::serial;
 use std::fs::File;
 use std::io::Cursor;
 use std::io::LineWriter;
 use std::io::Write;
+use tempfile::tempdir;
 
 const OUT_FILE_P1: &str = "with_1_var.bgen";
 const OUT_FILE_P2: &str = "with_99_var.bgen";
@@ -34,6 +37,7 @@ fn filtering_then_merging() {
         merge_name.to_string(),
         merge_output.to_string(),
         OUT_FILE_P1.to_string(),
+        None,
     )
     .unwrap();
     assert_bgen_equality("data_test/samp_100_var_100.bgen", merge_output);
@@ -43,6 +47,74 @@ fn filtering_then_merging() {
     std::fs::remove_file(merge_name).unwrap();
 }
 
+#[test]
+#[serial]
+fn merging_with_chr_renaming() {
+    let dir = tempdir().unwrap();
+    let part_1 = dir.path().join("part_1.bgen");
+    let part_2 = dir.path().join("part_2.bgen");
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream
+        .with_chr_renamer(Some(ChrRenamer::Convention(ChrConvention::Numeric)))
+        .to_bgen(part_1.to_str().unwrap(), false)
+        .unwrap();
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_excl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream
+        .with_chr_renamer(Some(ChrRenamer::Convention(ChrConvention::Ucsc)))
+        .to_bgen(part_2.to_str().unwrap(), false)
+        .unwrap();
+    let merge_name = dir.path().join("tmp.merge");
+    std::fs::write(&merge_name, part_2.to_str().unwrap()).unwrap();
+    let merge_output = dir.path().join("merged.bgen");
+    bgen_merge(
+        merge_name.to_str().unwrap().to_string(),
+        merge_output.to_str().unwrap().to_string(),
+        part_1.to_str().unwrap().to_string(),
+        Some(ChrRenamer::Convention(ChrConvention::Ensembl)),
+    )
+    .unwrap();
+    let mut merged = BgenStream::from_path(merge_output.to_str().unwrap(), false, true).unwrap();
+    merged.read_offset_and_header().unwrap();
+    let variants_merged: Vec<_> = merged.map(|r| r.unwrap()).collect();
+    let variants_original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    assert_eq!(100, variants_merged.len());
+    assert!(variants_merged.iter().all(|v| v.chr == "1"));
+    // the merge file lists part 2 first, data blocks are copied unchanged
+    for variant in variants_original {
+        assert!(variants_merged.contains(&variant));
+    }
+}
+
+#[test]
+fn merging_files_with_other_samples_is_an_error() {
+    let dir = tempdir().unwrap();
+    let other = dir.path().join("other_samples.bgen");
+    let mut writer = BgenWriter::builder(1)
+        .with_samples(vec!["sample_1".to_string()])
+        .create(other.to_str().unwrap())
+        .unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant("", "rs1", "1", 10, &alleles, &[1.0, 0.0, 0.0])
+        .unwrap();
+    writer.finish().unwrap();
+    let merge_name = dir.path().join("tmp.merge");
+    std::fs::write(&merge_name, other.to_str().unwrap()).unwrap();
+    let merge_output = dir.path().join("merged.bgen");
+    let result = bgen_merge(
+        merge_name.to_str().unwrap().to_string(),
+        merge_output.to_str().unwrap().to_string(),
+        "data_test/samp_100_var_100.bgen".to_string(),
+        None,
+    );
+    assert!(result.is_err());
+    assert!(!merge_output.exists());
+}
+
 fn create_bgen_and_read() -> BgenStream<Cursor<Vec<u8>>> {
     let bgen_bytes = include_bytes!("../data_test/samp_100_var_100.bgen");
     let mut bgen_stream = BgenStream::from_bytes(bgen_bytes.to_vec(), true).unwrap();
@@ -0,0 +1,87 @@
+extern crate bgen_reader;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::bgen::samples::SamplePolicy;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::{tempdir, TempDir};
+
+#[test]
+fn sample_file_matches_embedded() {
+    let (_dir, bgen_path) = copy_bgen_with_sample_file(|i| format!("HG{} HG{}", i, i));
+    let embedded = read_samples(&bgen_path, false, SamplePolicy::RequireMatch).unwrap();
+    let (_dir, bgen_path) = copy_bgen_with_sample_file(|i| embedded[i].clone() + " other");
+    let samples = read_samples(&bgen_path, true, SamplePolicy::RequireMatch).unwrap();
+    assert_eq!(embedded, samples);
+}
+
+#[test]
+fn require_match_reports_first_mismatch() {
+    let (_dir, bgen_path) = copy_bgen_with_sample_file(|i| format!("id{} id{}", i, i));
+    let err = read_samples(&bgen_path, true, SamplePolicy::RequireMatch).unwrap_err();
+    assert!(err.to_string().contains("sample 0 is"), "{}", err);
+}
+
+#[test]
+fn prefer_sample_file() {
+    let (_dir, bgen_path) = copy_bgen_with_sample_file(|i| format!("id{} other{}", i, i));
+    let samples = read_samples(&bgen_path, true, SamplePolicy::PreferSampleFile).unwrap();
+    assert_eq!("id0", samples[0]);
+    assert_eq!("id99", samples[99]);
+    let samples = read_samples(&bgen_path, true, SamplePolicy::PreferEmbedded).unwrap();
+    assert_eq!("AFR_ACB-HG01879", samples[0]);
+}
+
+#[test]
+fn match_on_id_2() {
+    let (_dir, bgen_path) = copy_bgen_with_sample_file(|i| format!("fam{} HG{}", i, i));
+    let embedded = read_samples(&bgen_path, false, SamplePolicy::MatchId2).unwrap();
+    let (_dir, bgen_path) =
+        copy_bgen_with_sample_file(|i| format!("fam{} {}", i, embedded[i].clone()));
+    let samples = read_samples(&bgen_path, true, SamplePolicy::MatchId2).unwrap();
+    assert_eq!(embedded, samples);
+    let err = read_samples(&bgen_path, true, SamplePolicy::RequireMatch).unwrap_err();
+    assert!(err.to_string().contains("ID_1"), "{}", err);
+}
+
+#[test]
+fn sample_file_ids_written_to_bgen() {
+    let (dir, bgen_path) = copy_bgen_with_sample_file(|i| format!("id{} other{}", i, i));
+    let mut bgen_stream = BgenStream::from_path(bgen_path.to_str().unwrap(), true, true)
+        .unwrap()
+        .with_sample_policy(SamplePolicy::PreferSampleFile);
+    bgen_stream.read_offset_and_header().unwrap();
+    let out_path = dir.path().join("renamed.bgen");
+    bgen_stream
+        .to_bgen(out_path.to_str().unwrap(), false)
+        .unwrap();
+    let samples = read_samples(&out_path, false, SamplePolicy::RequireMatch).unwrap();
+    assert_eq!(100, samples.len());
+    assert_eq!("id42", samples[42]);
+}
+
+fn read_samples(
+    bgen_path: &Path,
+    use_sample_file: bool,
+    policy: SamplePolicy,
+) -> color_eyre::Result<Vec<String>> {
+    let mut bgen_stream =
+        BgenStream::from_path(bgen_path.to_str().unwrap(), use_sample_file, true)?
+            .with_sample_policy(policy);
+    bgen_stream.read_offset_and_header()?;
+    let samples = bgen_stream.samples.clone();
+    assert_eq!(100, bgen_stream.count());
+    Ok(samples)
+}
+
+fn copy_bgen_with_sample_file(line: impl Fn(usize) -> String) -> (TempDir, PathBuf) {
+    let dir = tempdir().unwrap();
+    let bgen_path = dir.path().join("samp_100_var_100.bgen");
+    std::fs::copy("data_test/samp_100_var_100.bgen", &bgen_path).unwrap();
+    let mut file = std::fs::File::create(bgen_path.with_extension("sample")).unwrap();
+    writeln!(file, "ID_1 ID_2 missing sex").unwrap();
+    writeln!(file, "0 0 0 D").unwrap();
+    for i in 0..100 {
+        writeln!(file, "{} 0 1", line(i)).unwrap();
+    }
+    (dir, bgen_path)
+}
@@ -0,0 +1,149 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::{BgenStream, MetadataBgi};
+use bgen_reader::bgen::writer::BgenWriter;
+use bgen_reader::vcf_writer::write_vcf_to;
+use common::{create_bgen_and_read, read_bytes};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn read_from_any_reader() {
+    let bgen_bytes = include_bytes!("../data_test/samp_100_var_100.bgen");
+    let mut bgen_stream = BgenStream::from_reader(&bgen_bytes[..], true);
+    bgen_stream.read_offset_and_header().unwrap();
+    assert!(matches!(bgen_stream.metadata, MetadataBgi::Stream));
+    assert_eq!(create_bgen_and_read().samples, bgen_stream.samples);
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    let streamed: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    assert_eq!(original, streamed);
+}
+
+#[test]
+fn read_from_decompressing_reader() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder
+        .write_all(include_bytes!("../data_test/samp_100_var_100.bgen"))
+        .unwrap();
+    let compressed = encoder.finish().unwrap();
+    let mut bgen_stream = BgenStream::from_reader(GzDecoder::new(&compressed[..]), true);
+    bgen_stream.read_offset_and_header().unwrap();
+    let mut vcf = Vec::new();
+    write_vcf_to(&mut vcf, bgen_stream).unwrap();
+    let mut expected = Vec::new();
+    write_vcf_to(&mut expected, create_bgen_and_read()).unwrap();
+    assert_eq!(expected, vcf);
+}
+
+#[test]
+fn from_input_opens_files() {
+    let mut bgen_stream = BgenStream::from_input(BGEN_PATH, false, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    assert!(matches!(bgen_stream.metadata, MetadataBgi::File(_)));
+    let rsids: Vec<_> = bgen_stream.map(|r| r.unwrap().rsid).collect();
+    let expected: Vec<_> = create_bgen_and_read().map(|r| r.unwrap().rsid).collect();
+    assert_eq!(expected, rsids);
+}
+
+#[test]
+fn files_smaller_than_1000_bytes_open_by_path() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("small.bgen");
+    let output = output.to_str().unwrap();
+    let mut writer = BgenWriter::builder(1).create(output).unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant("var_1", "rs1", "1", 10, &alleles, &[1.0, 0.0, 0.0])
+        .unwrap();
+    writer.finish().unwrap();
+    let file_size = std::fs::metadata(output).unwrap().len();
+    assert!(file_size < 1000);
+    let mut bgen_stream = BgenStream::from_input(output, false, true).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    match &bgen_stream.metadata {
+        MetadataBgi::File(metadata) => {
+            assert_eq!(file_size as usize, metadata.first_1000_bytes.len())
+        }
+        _ => panic!("expected file metadata"),
+    }
+    assert_eq!(1, bgen_stream.count());
+}
+
+#[test]
+fn truncated_input_is_an_error() {
+    let bgen_bytes = include_bytes!("../data_test/samp_100_var_100.bgen");
+    let truncated = &bgen_bytes[..bgen_bytes.len() / 2];
+    let mut bgen_stream = BgenStream::from_reader(truncated, true);
+    bgen_stream.read_offset_and_header().unwrap();
+    let variants: Vec<_> = bgen_stream.collect();
+    assert!(variants.last().unwrap().is_err());
+    assert!(variants[..variants.len() - 1].iter().all(|r| r.is_ok()));
+
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("truncated.bgen");
+    for args in [
+        vec!["list"],
+        vec!["vcf", "-"],
+        vec!["bgen", output.to_str().unwrap()],
+    ] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bgen_reader"))
+            .args(["-f", "-"])
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        // the command may fail before reading the whole input
+        let _ = child.stdin.take().unwrap().write_all(truncated);
+        assert!(!child.wait().unwrap().success(), "{:?}", args);
+    }
+}
+
+#[test]
+fn standard_input_to_standard_output_is_rejected() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bgen_reader"))
+        .args(["-f", "-", "bgen", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let _ = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(include_bytes!("../data_test/samp_100_var_100.bgen"));
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("standard output"));
+}
+
+#[test]
+fn short_data_block_is_an_error() {
+    let mut writer = BgenWriter::builder(1)
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant("", "rs1", "1", 10, &alleles, &[1.0, 0.0, 0.0])
+        .unwrap();
+    let mut bgen_bytes = writer.into_inner().unwrap().into_inner();
+    let variant = read_bytes(bgen_bytes.clone(), false)
+        .next()
+        .unwrap()
+        .unwrap();
+    // the compressed data block is too short to hold its uncompressed length
+    let length_offset = variant.file_start_position + 26;
+    bgen_bytes[length_offset..length_offset + 4].copy_from_slice(&2u32.to_le_bytes());
+    bgen_bytes.truncate(length_offset + 6);
+    let mut bgen_stream = read_bytes(bgen_bytes, true);
+    assert!(bgen_stream.next().unwrap().is_err());
+}
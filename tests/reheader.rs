@@ -0,0 +1,67 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::reheader::{reheader_bgen, SampleRewrite};
+use bgen_reader::bgen::samples::read_sample_file;
+use common::{assert_same_variants, create_bgen_and_read, read_path};
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn mapping_sample_identifiers() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("pseudo.bgen");
+    let output = output.to_str().unwrap();
+    let original_samples = create_bgen_and_read().samples;
+    let mapping: HashMap<_, _> = original_samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.clone(), format!("pseudo_{}", i)))
+        .collect();
+    reheader_bgen(BGEN_PATH, output, &SampleRewrite::Mapping(mapping)).unwrap();
+    let (_, samples, variants) = read_path(output);
+    assert_eq!(100, samples.len());
+    assert_eq!("pseudo_0", samples[0]);
+    assert_eq!("pseudo_99", samples[99]);
+    assert_same_variants(&variants, 100);
+}
+
+#[test]
+fn removing_and_adding_sample_identifiers() {
+    let dir = tempdir().unwrap();
+    let removed = dir.path().join("removed.bgen");
+    let removed = removed.to_str().unwrap();
+    reheader_bgen(BGEN_PATH, removed, &SampleRewrite::Remove).unwrap();
+    let (header, samples, variants) = read_path(removed);
+    assert!(!header.header_flags.sample_id_present);
+    assert!(samples.is_empty());
+    assert_eq!(20, header.start_data_offset);
+    assert_same_variants(&variants, 100);
+
+    let added = dir.path().join("added.bgen");
+    let added = added.to_str().unwrap();
+    let new_samples: Vec<_> = read_sample_file("data_test/samp_100_var_100.sample")
+        .unwrap()
+        .into_iter()
+        .map(|e| e.id_1)
+        .collect();
+    reheader_bgen(removed, added, &SampleRewrite::Replace(new_samples.clone())).unwrap();
+    let (header, samples, variants) = read_path(added);
+    assert!(header.header_flags.sample_id_present);
+    assert_eq!(new_samples, samples);
+    assert_same_variants(&variants, 100);
+}
+
+#[test]
+fn incomplete_rewrites_fail() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("output.bgen");
+    let output = output.to_str().unwrap();
+    let mapping = HashMap::from([("AFR_ACB-HG01879".to_string(), "pseudo".to_string())]);
+    let error = reheader_bgen(BGEN_PATH, output, &SampleRewrite::Mapping(mapping)).unwrap_err();
+    assert!(error.to_string().contains("missing from the mapping"));
+    let replace = SampleRewrite::Replace(vec!["only_one".to_string()]);
+    let error = reheader_bgen(BGEN_PATH, output, &replace).unwrap_err();
+    assert!(error.to_string().contains("1 new identifiers"));
+}
@@ -0,0 +1,169 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::{BgenStream, RequantisationError};
+use bgen_reader::bgen::variant_data::{DataBlock, SampleProbabilities, VariantData};
+use bgen_reader::bgen::writer::BgenWriter;
+use common::{create_bgen_and_read, read_variants};
+use tempfile::tempdir;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn requantise_to_8_bits() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("8_bits.bgen");
+    let output = output.to_str().unwrap();
+    let reported_error = requantise(BGEN_PATH, output, 8).unwrap();
+    let variants = read_variants(output);
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    assert_eq!(original.len(), variants.len());
+    let mut max_error = 0f64;
+    for (variant, copy) in original.iter().zip(variants.iter()) {
+        assert_eq!(8, copy.data_block.bytes_probability);
+        assert_sums_to_one(&copy.data_block);
+        let error = variant
+            .data_block
+            .max_dosage_error(&copy.data_block)
+            .unwrap();
+        max_error = max_error.max(error);
+    }
+    // the test file holds hard calls, which every bit depth stores exactly
+    assert_eq!(max_error, reported_error.max_dosage_error);
+    assert_eq!(0.0, reported_error.max_dosage_error);
+    assert_eq!(0, reported_error.num_not_biallelic);
+}
+
+#[test]
+fn requantise_fractional_probabilities() {
+    let samples: Vec<_> = [[0.1, 0.6, 0.3], [0.333, 0.333, 0.334], [0.0, 0.0, 1.0]]
+        .iter()
+        .map(|probabilities| SampleProbabilities {
+            ploidy: 2,
+            missing: false,
+            probabilities: probabilities.to_vec(),
+        })
+        .collect();
+    let data_block = DataBlock::from_sample_probabilities(&samples, 2, false, 16).unwrap();
+    let requantised = data_block.with_bits(8).unwrap();
+    assert_eq!(8, requantised.bytes_probability);
+    assert_sums_to_one(&requantised);
+    let error = data_block.max_dosage_error(&requantised).unwrap();
+    assert!(error > 0.0);
+    assert!(error < 2.0 / 255.0);
+    let requantised = data_block.with_bits(2).unwrap();
+    assert_sums_to_one(&requantised);
+    assert!(data_block.max_dosage_error(&requantised).unwrap() < 2.0 / 3.0);
+}
+
+#[test]
+fn requantise_file_with_fractional_probabilities() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("fractional.bgen");
+    let input = input.to_str().unwrap();
+    let mut writer = BgenWriter::builder(3).create(input).unwrap();
+    let biallelic = ["A".to_string(), "G".to_string()];
+    writer
+        .write_variant(
+            "",
+            "rs1",
+            "1",
+            10,
+            &biallelic,
+            &[0.1, 0.6, 0.3, 0.333, 0.333, 0.334, 0.0, 0.0, 1.0],
+        )
+        .unwrap();
+    writer
+        .write_variant(
+            "",
+            "rs2",
+            "1",
+            20,
+            &biallelic,
+            &[0.25, 0.5, 0.25, 0.9, 0.05, 0.05, 0.01, 0.98, 0.01],
+        )
+        .unwrap();
+    let triallelic = ["A".to_string(), "G".to_string(), "T".to_string()];
+    let mut probabilities = vec![0.1, 0.2, 0.3, 0.15, 0.15, 0.1];
+    probabilities.extend([1.0, 0.0, 0.0, 0.0, 0.0, 0.0].repeat(2));
+    writer
+        .write_variant("", "rs3", "1", 30, &triallelic, &probabilities)
+        .unwrap();
+    writer.finish().unwrap();
+
+    let output = dir.path().join("fractional_8_bits.bgen");
+    let output = output.to_str().unwrap();
+    let reported_error = requantise(input, output, 8).unwrap();
+    let mut max_error = 0f64;
+    for (variant, copy) in read_variants(input)
+        .iter()
+        .zip(read_variants(output).iter())
+    {
+        if let Some(error) = variant.data_block.max_dosage_error(&copy.data_block) {
+            max_error = max_error.max(error);
+        }
+    }
+    assert!(reported_error.max_dosage_error > 0.0);
+    assert_eq!(max_error, reported_error.max_dosage_error);
+    assert_eq!(1, reported_error.num_not_biallelic);
+}
+
+#[test]
+fn requantise_to_same_bits_is_lossless() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("16_bits.bgen");
+    let output = output.to_str().unwrap();
+    let reported_error = requantise(BGEN_PATH, output, 16).unwrap();
+    assert_eq!(0.0, reported_error.max_dosage_error);
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    for (variant, copy) in original.iter().zip(read_variants(output).iter()) {
+        assert_eq!(variant.data_block, copy.data_block);
+    }
+}
+
+#[test]
+fn requantised_vcf_keeps_probabilities() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("8_bits.bgen");
+    let output = output.to_str().unwrap();
+    requantise(BGEN_PATH, output, 8);
+    let original = create_bgen_and_read().next().unwrap().unwrap();
+    let copy = read_variants(output).remove(0);
+    let original_gp = vcf_probabilities(&original);
+    let copy_gp = vcf_probabilities(&copy);
+    assert_eq!(original_gp.len(), copy_gp.len());
+    for (p, q) in original_gp.iter().zip(copy_gp.iter()) {
+        assert!((p - q).abs() < 1.0 / 255.0);
+    }
+}
+
+fn assert_sums_to_one(data_block: &DataBlock) {
+    for sample in data_block.sample_probabilities() {
+        let sum: f64 = sample.probabilities.iter().sum();
+        assert!((sum - 1.0).abs() < 1e-9);
+    }
+}
+
+fn vcf_probabilities(variant: &VariantData) -> Vec<f64> {
+    let mut line = Vec::new();
+    variant.write_vcf_line(&mut line).unwrap();
+    String::from_utf8(line)
+        .unwrap()
+        .split('\t')
+        .skip(9)
+        .filter(|field| !field.trim().is_empty())
+        .flat_map(|field| {
+            let gp = field.split(':').nth(1).unwrap().trim().to_string();
+            gp.split(',')
+                .map(|p| p.parse::<f64>().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn requantise(input: &str, output: &str, bits: u8) -> Option<RequantisationError> {
+    let mut bgen_stream = BgenStream::from_path(input, false, true)
+        .unwrap()
+        .with_output_bits(Some(bits));
+    bgen_stream.read_offset_and_header().unwrap();
+    bgen_stream.to_bgen(output, false).unwrap()
+}
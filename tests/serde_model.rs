@@ -0,0 +1,48 @@
+#![cfg(feature = "serde")]
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::{BgenStream, MetadataBgi};
+use bgen_reader::bgen::header::Header;
+use bgen_reader::bgen::variant_data::VariantData;
+use bgen_reader::parser::Range;
+use common::create_bgen_and_read;
+
+#[test]
+fn variants_round_trip() {
+    let bgen_stream = create_bgen_and_read();
+    let header = bgen_stream.header.clone();
+    let json = serde_json::to_string(&header).unwrap();
+    assert_eq!(header, serde_json::from_str::<Header>(&json).unwrap());
+
+    for variant in bgen_stream.take(5) {
+        let variant = variant.unwrap();
+        let json = serde_json::to_string(&variant).unwrap();
+        let copy: VariantData = serde_json::from_str(&json).unwrap();
+        assert_eq!(variant, copy);
+        assert_eq!(variant.file_start_position, copy.file_start_position);
+    }
+}
+
+#[test]
+fn range_and_metadata_round_trip() {
+    let range = Range::from_str("1:100-200", true).unwrap();
+    let json = serde_json::to_value(&range).unwrap();
+    assert_eq!("1", json["chr"]);
+    assert_eq!(100, json["start"]);
+    let copy: Range = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        (range.chr, range.start, range.end),
+        (copy.chr, copy.start, copy.end)
+    );
+
+    let bgen_stream =
+        BgenStream::from_path("data_test/samp_100_var_100.bgen", false, false).unwrap();
+    let json = serde_json::to_string(&bgen_stream.metadata).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+        MetadataBgi::File(file_metadata) => {
+            assert_eq!("samp_100_var_100.bgen", file_metadata.filename);
+            assert_eq!(13236, file_metadata.file_size);
+        }
+        _ => panic!("file metadata expected"),
+    }
+}
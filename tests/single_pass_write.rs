@@ -0,0 +1,66 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+use bgen_reader::parser::FilterArgs;
+use common::create_bgen_and_read;
+use std::io::{Cursor, Seek, SeekFrom, Write};
+
+const RANGE: &str = "1:0-952567";
+
+#[test]
+fn single_pass_from_stream() {
+    let bgen_bytes = include_bytes!("../data_test/samp_100_var_100.bgen");
+    let mut bgen_stream = BgenStream::from_reader(&bgen_bytes[..], true);
+    bgen_stream.read_offset_and_header().unwrap();
+    bgen_stream.collect_filters(filter_args()).unwrap();
+    let mut output = Cursor::new(Vec::new());
+    bgen_stream.write_bgen_seekable(&mut output, false).unwrap();
+
+    let mut copy = BgenStream::from_bytes(output.into_inner(), true).unwrap();
+    copy.read_offset_and_header().unwrap();
+    let mut expected = create_bgen_and_read();
+    expected.collect_filters(filter_args()).unwrap();
+    let expected: Vec<_> = expected.map(|r| r.unwrap()).collect();
+    assert!(!expected.is_empty() && expected.len() < 100);
+    assert_eq!(expected.len() as u32, copy.header.variant_num);
+    let copied: Vec<_> = copy.map(|r| r.unwrap()).collect();
+    assert_eq!(expected, copied);
+}
+
+#[test]
+fn single_pass_matches_two_passes() {
+    let mut bgen_stream = create_bgen_and_read();
+    bgen_stream.collect_filters(filter_args()).unwrap();
+    let mut two_passes = Vec::new();
+    bgen_stream.write_bgen(&mut two_passes, false).unwrap();
+
+    let mut bgen_stream = create_bgen_and_read();
+    bgen_stream.collect_filters(filter_args()).unwrap();
+    let mut single_pass = Cursor::new(Vec::new());
+    bgen_stream
+        .write_bgen_seekable(&mut single_pass, false)
+        .unwrap();
+    assert_eq!(two_passes, single_pass.into_inner());
+}
+
+#[test]
+fn single_pass_after_existing_content() {
+    let prefix = b"not a bgen file";
+    let mut output = Cursor::new(Vec::new());
+    output.write_all(prefix).unwrap();
+    create_bgen_and_read()
+        .write_bgen_seekable(&mut output, false)
+        .unwrap();
+    assert_eq!(output.get_ref().len() as u64, output.position());
+    output.seek(SeekFrom::Start(0)).unwrap();
+    let bytes = output.into_inner();
+    assert_eq!(prefix, &bytes[..prefix.len()]);
+    let mut copy = BgenStream::from_bytes(bytes[prefix.len()..].to_vec(), true).unwrap();
+    copy.read_offset_and_header().unwrap();
+    assert_eq!(100, copy.header.variant_num);
+    assert_eq!(100, copy.count());
+}
+
+fn filter_args() -> FilterArgs {
+    FilterArgs::default().with_range_incl_str(RANGE.to_string())
+}
@@ -0,0 +1,122 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::bgen_merge;
+use bgen_reader::bgen::chromosome::ChrOrder;
+use bgen_reader::bgen::sort::{is_sorted, sort_bgen};
+use bgen_reader::bgen::writer::BgenWriter;
+use bgen_reader::parser::FilterArgs;
+use common::{create_bgen_and_read, open_path};
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn sorted_input_detected() {
+    let bgen_stream = create_bgen_and_read();
+    assert!(is_sorted(bgen_stream, &ChrOrder::Natural).unwrap());
+}
+
+#[test]
+fn unsorted_input_detected() {
+    let dir = tempdir().unwrap();
+    let unsorted = create_unsorted_bgen(dir.path());
+    assert!(!is_sorted(open_path(&unsorted), &ChrOrder::Natural).unwrap());
+}
+
+#[test]
+fn duplicates_with_different_block_sizes_are_sorted() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("duplicates.bgen");
+    let output = output.to_str().unwrap();
+    let mut writer = BgenWriter::builder(50).create(output).unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    let spread: Vec<_> = (0..50)
+        .flat_map(|i| {
+            let p = i as f64 / 100.0;
+            [p, 0.5, 0.5 - p]
+        })
+        .collect();
+    let hard_calls: Vec<_> = (0..50).flat_map(|_| [1.0, 0.0, 0.0]).collect();
+    for probabilities in [&spread, &hard_calls] {
+        writer
+            .write_variant("var_1", "rs1", "1", 10, &alleles, probabilities)
+            .unwrap();
+    }
+    writer.finish().unwrap();
+    let sizes: Vec<_> = open_path(output)
+        .map(|r| r.unwrap().size_in_bytes)
+        .collect();
+    assert!(sizes[0] > sizes[1]);
+    assert!(is_sorted(open_path(output), &ChrOrder::Natural).unwrap());
+}
+
+#[test]
+fn sorting_restores_order() {
+    let dir = tempdir().unwrap();
+    let unsorted = create_unsorted_bgen(dir.path());
+    let output = dir.path().join("sorted.bgen");
+    let output = output.to_str().unwrap();
+    sort_bgen(&unsorted, output, &ChrOrder::Natural, 1_000_000).unwrap();
+    assert!(is_sorted(open_path(output), &ChrOrder::Natural).unwrap());
+    let sorted: Vec<_> = open_path(output).map(|r| r.unwrap()).collect();
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    assert_eq!(100, sorted.len());
+    for (variant, copy) in original.iter().zip(sorted.iter()) {
+        assert_eq!(variant.rsid, copy.rsid);
+        assert_eq!(variant.alleles, copy.alleles);
+        assert_eq!(variant.data_block, copy.data_block);
+    }
+}
+
+#[test]
+fn sorting_with_chunk_files() {
+    let dir = tempdir().unwrap();
+    let unsorted = create_unsorted_bgen(dir.path());
+    let in_memory = dir.path().join("in_memory.bgen");
+    let chunked = dir.path().join("chunked.bgen");
+    let in_memory = in_memory.to_str().unwrap();
+    let chunked = chunked.to_str().unwrap();
+    sort_bgen(&unsorted, in_memory, &ChrOrder::Natural, 1_000_000).unwrap();
+    sort_bgen(&unsorted, chunked, &ChrOrder::Natural, 7).unwrap();
+    assert_eq!(
+        std::fs::read(in_memory).unwrap(),
+        std::fs::read(chunked).unwrap()
+    );
+    // chunk files are removed once merged
+    let leftovers = std::fs::read_dir(dir.path())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_str().unwrap().contains("sort_chunk")
+        })
+        .count();
+    assert_eq!(0, leftovers);
+}
+
+/// Writes the second half of the test file before the first half
+fn create_unsorted_bgen(dir: &Path) -> String {
+    let first_half = dir.join("first_half.bgen");
+    let second_half = dir.join("second_half.bgen");
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream
+        .to_bgen(first_half.to_str().unwrap(), false)
+        .unwrap();
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_excl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream
+        .to_bgen(second_half.to_str().unwrap(), false)
+        .unwrap();
+    let merge_name = dir.join("unsorted.merge");
+    std::fs::write(&merge_name, second_half.to_str().unwrap()).unwrap();
+    let unsorted = dir.join("unsorted.bgen");
+    bgen_merge(
+        merge_name.to_str().unwrap().to_string(),
+        unsorted.to_str().unwrap().to_string(),
+        first_half.to_str().unwrap().to_string(),
+        None,
+    )
+    .unwrap();
+    unsorted.to_str().unwrap().to_string()
+}
@@ -0,0 +1,155 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::bgen_stream::{bgen_merge, BgenStream};
+use bgen_reader::bgen::chromosome::ChrRenamer;
+use bgen_reader::bgen::split::{split_bgen, SplitMode};
+use bgen_reader::bgen::writer::BgenWriter;
+use bgen_reader::parser::FilterArgs;
+use common::{create_bgen_and_read, read_bytes, read_variants};
+use std::io::Cursor;
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn split_in_chunks_of_variants() {
+    let dir = tempdir().unwrap();
+    let prefix = dir.path().join("piece");
+    let output_paths = split_bgen(
+        create_bgen_and_read(),
+        prefix.to_str().unwrap(),
+        SplitMode::Variants,
+        3,
+        false,
+    )
+    .unwrap();
+    assert_eq!(3, output_paths.len());
+    assert!(output_paths[0].ends_with("piece.chunk_1.bgen"));
+    let pieces: Vec<Vec<_>> = output_paths.iter().map(|p| read_variants(p)).collect();
+    assert_eq!(
+        vec![33, 33, 34],
+        pieces.iter().map(|p| p.len()).collect::<Vec<_>>()
+    );
+    let original: Vec<_> = create_bgen_and_read().map(|r| r.unwrap()).collect();
+    let rejoined: Vec<_> = pieces.into_iter().flatten().collect();
+    assert_eq!(original.len(), rejoined.len());
+    for (variant, copy) in original.iter().zip(rejoined.iter()) {
+        assert_eq!(variant.rsid, copy.rsid);
+        assert_eq!(variant.data_block, copy.data_block);
+    }
+}
+
+#[test]
+fn split_in_chunks_of_bytes_with_index() {
+    let dir = tempdir().unwrap();
+    let prefix = dir.path().join("piece");
+    let output_paths = split_bgen(
+        create_bgen_and_read(),
+        prefix.to_str().unwrap(),
+        SplitMode::Bytes,
+        4,
+        true,
+    )
+    .unwrap();
+    assert_eq!(4, output_paths.len());
+    let num_variants: usize = output_paths.iter().map(|p| read_variants(p).len()).sum();
+    assert_eq!(100, num_variants);
+    for output_path in output_paths.iter() {
+        let mut bgen_stream = BgenStream::from_path(output_path, false, false).unwrap();
+        bgen_stream.read_offset_and_header().unwrap();
+        assert_eq!(100, bgen_stream.samples.len());
+        assert!(Path::new(&format!("{}.bgi_rust", output_path)).exists());
+    }
+}
+
+#[test]
+fn split_per_chromosome_with_filters() {
+    let dir = tempdir().unwrap();
+    // the second part of the test file is moved to chromosome 2
+    let part_1 = dir.path().join("part_1.bgen");
+    let part_2 = dir.path().join("part_2.bgen");
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_range_incl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream
+        .to_bgen(part_1.to_str().unwrap(), false)
+        .unwrap();
+    let chr_map = dir.path().join("chr.map");
+    std::fs::write(&chr_map, "1\t2\n").unwrap();
+    let mut bgen_stream = create_bgen_and_read()
+        .with_chr_renamer(Some(ChrRenamer::from_mapping_file(&chr_map).unwrap()));
+    let list_args = FilterArgs::default().with_range_excl_str("1:0-952567".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream
+        .to_bgen(part_2.to_str().unwrap(), false)
+        .unwrap();
+    let merge_name = dir.path().join("two_chr.merge");
+    std::fs::write(&merge_name, part_1.to_str().unwrap()).unwrap();
+    let two_chr = dir.path().join("two_chr.bgen");
+    bgen_merge(
+        merge_name.to_str().unwrap().to_string(),
+        two_chr.to_str().unwrap().to_string(),
+        part_2.to_str().unwrap().to_string(),
+        None,
+    )
+    .unwrap();
+
+    let mut bgen_stream = BgenStream::from_path(two_chr.to_str().unwrap(), false, false).unwrap();
+    bgen_stream.read_offset_and_header().unwrap();
+    let list_args = FilterArgs::default().with_range_excl_str("2:0-1000000".to_string());
+    bgen_stream.collect_filters(list_args).unwrap();
+    let prefix = dir.path().join("per_chr");
+    let output_paths = split_bgen(
+        bgen_stream,
+        prefix.to_str().unwrap(),
+        SplitMode::Chromosome,
+        1,
+        false,
+    )
+    .unwrap();
+    assert_eq!(2, output_paths.len());
+    assert!(output_paths[0].ends_with("per_chr.1.bgen"));
+    assert!(output_paths[1].ends_with("per_chr.2.bgen"));
+    let chr_1 = read_variants(&output_paths[0]);
+    let chr_2 = read_variants(&output_paths[1]);
+    assert_eq!(read_variants(part_1.to_str().unwrap()).len(), chr_1.len());
+    assert!(chr_1.iter().all(|v| v.chr == "1"));
+    assert!(chr_2.iter().all(|v| v.chr == "2" && v.pos > 1000000));
+    let num_chr_2 = read_variants(part_2.to_str().unwrap())
+        .iter()
+        .filter(|v| v.pos > 1000000)
+        .count();
+    assert_eq!(num_chr_2, chr_2.len());
+}
+
+#[test]
+fn chromosome_names_are_sanitized_in_file_names() {
+    let dir = tempdir().unwrap();
+    let mut writer = BgenWriter::builder(1)
+        .build(Cursor::new(Vec::new()))
+        .unwrap();
+    let alleles = ["A".to_string(), "G".to_string()];
+    for (i, chr) in ["HLA-A*01:01/alt", "2", "HLA-A*01:01/alt"]
+        .iter()
+        .enumerate()
+    {
+        writer
+            .write_variant("", &format!("rs{}", i), chr, 10, &alleles, &[1.0, 0.0, 0.0])
+            .unwrap();
+    }
+    let bgen_bytes = writer.into_inner().unwrap().into_inner();
+    let prefix = dir.path().join("piece");
+    let output_paths = split_bgen(
+        read_bytes(bgen_bytes, false),
+        prefix.to_str().unwrap(),
+        SplitMode::Chromosome,
+        0,
+        false,
+    )
+    .unwrap();
+    assert_eq!(2, output_paths.len());
+    assert!(output_paths[0].ends_with("piece.HLA-A_01_01_alt.bgen"));
+    let variants = read_variants(&output_paths[0]);
+    assert_eq!(2, variants.len());
+    assert!(variants.iter().all(|v| v.chr == "HLA-A*01:01/alt"));
+    assert_eq!(1, read_variants(&output_paths[1]).len());
+}
@@ -0,0 +1,51 @@
+extern crate bgen_reader;
+mod common;
+use bgen_reader::bgen::stats::{write_stats, VariantStats};
+use common::create_bgen_and_read;
+use tempfile::tempdir;
+
+#[test]
+fn stats_first_variant() {
+    let bgen_stream = create_bgen_and_read();
+    let variant_data: Vec<_> = bgen_stream.map(|r| r.unwrap()).collect();
+    let stats = VariantStats::from_variant(&variant_data[0]);
+    let dosages: Vec<f64> = variant_data[0]
+        .data_block
+        .dosages()
+        .unwrap()
+        .into_iter()
+        .map(|d| d.unwrap())
+        .collect();
+    assert_eq!(100, stats.total);
+    assert_eq!(0, stats.missing_count);
+    assert_eq!(100f64, stats.genotype_counts.iter().sum::<f64>());
+    assert_eq!(dosages.iter().sum::<f64>(), stats.allele_b_count);
+    assert_eq!(200f64, stats.allele_a_count + stats.allele_b_count);
+    assert!(stats.minor_allele_frequency <= 0.5);
+    // hard calls carry no imputation uncertainty
+    assert!((stats.impute_info - 1f64).abs() < 1e-9);
+    assert!((0f64..=1f64).contains(&stats.hwe_p_value));
+}
+
+#[test]
+fn dosages_of_multiallelic_variant_fail() {
+    let mut variant_data = create_bgen_and_read().next().unwrap().unwrap();
+    assert!(variant_data.data_block.dosages().is_ok());
+    variant_data.data_block.number_alleles = 3;
+    assert!(variant_data.data_block.dosages().is_err());
+}
+
+#[test]
+fn stats_all_variants_written() {
+    let bgen_stream = create_bgen_and_read();
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("stats.tsv");
+    write_stats(output.to_str().unwrap(), bgen_stream).unwrap();
+    let content = std::fs::read_to_string(output).unwrap();
+    let lines: Vec<_> = content.lines().collect();
+    assert_eq!(101, lines.len());
+    assert!(lines[0].starts_with("alternate_ids\trsid\tchromosome\tposition"));
+    let first: Vec<_> = lines[1].split('\t').collect();
+    assert_eq!(lines[0].split('\t').count(), first.len());
+    assert_eq!(["1_752566_G_A", "1", "752566", "G", "A"], first[1..6]);
+}
@@ -0,0 +1,24 @@
+extern crate bgen_reader;
+use bgen_reader::bgen::bgen_stream::BgenStream;
+
+const BGEN_PATH: &str = "data_test/samp_100_var_100.bgen";
+
+#[test]
+fn offsets_do_not_depend_on_reading_data_blocks() {
+    let offsets = |read_data_block: bool| -> Vec<_> {
+        let mut bgen_stream = BgenStream::from_path(BGEN_PATH, false, read_data_block).unwrap();
+        bgen_stream.read_offset_and_header().unwrap();
+        bgen_stream
+            .map(|r| {
+                let variant_data = r.unwrap();
+                (variant_data.file_start_position, variant_data.size_in_bytes)
+            })
+            .collect()
+    };
+    let skipped = offsets(false);
+    assert_eq!(offsets(true), skipped);
+    assert_eq!((1732, 127), skipped[0]);
+    for pair in skipped.windows(2) {
+        assert_eq!(pair[0].0 + pair[0].1, pair[1].0);
+    }
+}
@@ -1,6 +1,6 @@
 extern crate bgen_reader;
 use bgen_reader::bgen::bgen_stream::BgenStream;
-use bgen_reader::parser::FilterArgs;
+use bgen_reader::parser::{FilterArgs, StatFilters};
 use serial_test::serial;
 use std::io::Cursor;
 
@@ -62,6 +62,24 @@ fn filtering_rsid_on_bgen_write() {
     std::fs::remove_file(OUT_FILE).unwrap();
 }
 
+#[test]
+#[serial]
+fn filtering_stats_on_bgen_write() {
+    let mut bgen_stream = create_bgen_and_read();
+    let list_args = FilterArgs::default().with_stat_filters(StatFilters {
+        min_maf: Some(0.2),
+        ..Default::default()
+    });
+    bgen_stream.collect_filters(list_args).unwrap();
+    bgen_stream.to_bgen(OUT_FILE, false).unwrap();
+    let mut bgen_stream_test = BgenStream::from_path(OUT_FILE, false, true).unwrap();
+    bgen_stream_test.read_offset_and_header().unwrap();
+    assert_eq!(67, bgen_stream_test.header.variant_num);
+    let variant_data: Vec<_> = bgen_stream_test.map(|r| r.unwrap()).collect();
+    assert_eq!(67, variant_data.len());
+    std::fs::remove_file(OUT_FILE).unwrap();
+}
+
 fn create_bgen_and_read() -> BgenStream<Cursor<Vec<u8>>> {
     let bgen_bytes = include_bytes!("../data_test/samp_100_var_100.bgen");
     let mut bgen_stream = BgenStream::from_bytes(bgen_bytes.to_vec(), true).unwrap();